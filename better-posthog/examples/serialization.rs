@@ -0,0 +1,48 @@
+//! Measures serializing event batches into a body pre-allocated from the size of the previous one, as the HTTP client
+//! does, against a body growing from empty.
+//!
+//! Run with `cargo run --release --example serialization`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use better_posthog::Event;
+
+const BATCH_SIZES: [usize; 3] = [1, 100, 1000];
+const ITERATIONS: usize = 2000;
+
+fn main() {
+  for batch_size in BATCH_SIZES {
+    let batch: Vec<Event> = (0..batch_size)
+      .map(|index| {
+        let mut event = Event::new("page_view", "user_123");
+        event.insert_property("path", format!("/products/{index}"));
+        event.insert_property("referrer", "https://example.com/search?q=posthog");
+        event.insert_property("duration_ms", index * 10);
+        event
+      })
+      .collect();
+
+    let growing = measure(&batch, |_| Vec::new());
+    let presized = measure(&batch, Vec::with_capacity);
+    println!(
+      "{batch_size:>5} events: growing {:>9.2?}, pre-allocated {:>9.2?} per body ({:+.1}%)",
+      growing,
+      presized,
+      (presized.as_secs_f64() / growing.as_secs_f64() - 1.0) * 100.0
+    );
+  }
+}
+
+/// Returns the mean time to serialize the batch into a body allocated from the size of the previous one.
+fn measure(batch: &[Event], allocate: impl Fn(usize) -> Vec<u8>) -> Duration {
+  let mut previous_size = 0;
+  let start = Instant::now();
+  for _ in 0..ITERATIONS {
+    let mut body = allocate(previous_size);
+    serde_json::to_writer(&mut body, black_box(batch)).expect("events are serializable");
+    previous_size = body.len();
+    black_box(body);
+  }
+  start.elapsed() / u32::try_from(ITERATIONS).unwrap_or(u32::MAX)
+}
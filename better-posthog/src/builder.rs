@@ -0,0 +1,152 @@
+//! Fluent builder initializing the client.
+
+use std::time::Duration;
+
+use crate::client::{ApiKey, BeforeEnqueueFn, BeforeSendFn, ClientOptions, Host, LifecycleHookFn};
+use crate::{ClientGuard, Compression, Error, RetryConfig};
+
+/// Builder for [`ClientOptions`] that initializes the client, see [`builder`](crate::builder).
+///
+/// It sets the most common options; [`ClientBuilder::options`] gives access to the others.
+///
+/// # Examples
+///
+/// ```no_run
+/// use better_posthog::Host;
+///
+/// let _guard = better_posthog::builder()
+///   .api_key("phc_your_api_key")
+///   .host(Host::EU)
+///   .before_send(Box::new(|event| Some(event)))
+///   .init();
+/// ```
+#[derive(Debug, Default)]
+pub struct ClientBuilder {
+  options: ClientOptions,
+}
+
+impl ClientBuilder {
+  /// Sets the PostHog API key.
+  #[must_use]
+  pub fn api_key<T: Into<ApiKey>>(mut self, api_key: T) -> Self {
+    self.options.api_key = Some(api_key.into());
+    self
+  }
+
+  /// Sets the target PostHog host.
+  #[must_use]
+  pub fn host(mut self, host: Host) -> Self {
+    self.options.host = host;
+    self
+  }
+
+  /// Sets the timeout for graceful shutdown.
+  #[must_use]
+  pub const fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+    self.options.shutdown_timeout = timeout;
+    self
+  }
+
+  /// Appends a hook to modify or filter events before sending.
+  #[must_use]
+  pub fn before_send(mut self, hook: BeforeSendFn) -> Self {
+    self.options.before_send.push(hook);
+    self
+  }
+
+  /// Appends a hook to modify or filter events on the thread capturing them, before they are enqueued.
+  #[must_use]
+  pub fn before_enqueue(mut self, hook: BeforeEnqueueFn) -> Self {
+    self.options.before_enqueue.push(hook);
+    self
+  }
+
+  /// Sets the hook invoked on lifecycle transitions of the background worker.
+  #[must_use]
+  pub fn lifecycle_hook(mut self, hook: LifecycleHookFn) -> Self {
+    self.options.lifecycle_hook = Some(hook);
+    self
+  }
+
+  /// Sets the deployment environment of the application, set as the `environment` property of events.
+  #[must_use]
+  pub fn environment<S: Into<String>>(mut self, environment: S) -> Self {
+    self.options.environment = Some(environment.into());
+    self
+  }
+
+  /// Sets the release of the application, set as the `release` property of events.
+  #[must_use]
+  pub fn release<S: Into<String>>(mut self, release: S) -> Self {
+    self.options.release = Some(release.into());
+    self
+  }
+
+  /// Sets the compression of request bodies.
+  #[must_use]
+  pub const fn compression(mut self, compression: Compression) -> Self {
+    self.options.compression = compression;
+    self
+  }
+
+  /// Enables retries of requests that failed because PostHog is unavailable.
+  #[must_use]
+  pub const fn retry(mut self, retry: RetryConfig) -> Self {
+    self.options.retry = Some(retry);
+    self
+  }
+
+  /// Sets the timeout of each request to PostHog.
+  #[must_use]
+  pub const fn request_timeout(mut self, timeout: Duration) -> Self {
+    self.options.request_timeout = timeout;
+    self
+  }
+
+  /// Modifies the other options.
+  ///
+  /// # Examples
+  ///
+  /// ```no_run
+  /// let _guard = better_posthog::builder()
+  ///   .api_key("phc_your_api_key")
+  ///   .options(|options| options.auto_timestamp = true)
+  ///   .init();
+  /// ```
+  #[must_use]
+  pub fn options(mut self, configure: impl FnOnce(&mut ClientOptions)) -> Self {
+    configure(&mut self.options);
+    self
+  }
+
+  /// Returns the configured options.
+  #[must_use]
+  pub fn build(self) -> ClientOptions {
+    self.options
+  }
+
+  /// Initializes the client with the configured options, see [`init`](crate::init).
+  ///
+  /// # Panics
+  ///
+  /// Panics if the client was already initialized.
+  pub fn init(self) -> ClientGuard {
+    crate::init(self.options)
+  }
+
+  /// Initializes the client with the configured options, failing on invalid configuration,
+  /// see [`try_init`](crate::try_init).
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the options are invalid or the client was already initialized.
+  pub fn try_init(self) -> Result<ClientGuard, Error> {
+    crate::try_init(self.options)
+  }
+}
+
+impl From<ClientBuilder> for ClientOptions {
+  fn from(builder: ClientBuilder) -> Self {
+    builder.options
+  }
+}
@@ -0,0 +1,108 @@
+use std::time::{Duration, Instant};
+
+/// Configuration of the circuit breaker that pauses sending while PostHog is unavailable.
+///
+/// After `failure_threshold` consecutive failed requests (network errors or server errors), the circuit opens
+/// and events are dropped without being sent. Once `reset_timeout` elapses, a single request is let through
+/// as a probe while the others are still dropped: if it succeeds, the circuit closes; otherwise it stays open
+/// for another `reset_timeout`.
+///
+/// Only requests to the configured host count, so a failing destination of
+/// [`events::capture_to`](crate::events::capture_to) neither opens the circuit nor is paused by it.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let options = better_posthog::ClientOptions {
+///   api_key: Some("phc_your_api_key".into()),
+///   circuit_breaker: Some(better_posthog::CircuitBreakerConfig {
+///     failure_threshold: 3,
+///     reset_timeout: Duration::from_secs(60),
+///   }),
+///   ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone)]
+pub struct CircuitBreakerConfig {
+  /// Number of consecutive failures that opens the circuit (default: 5).
+  pub failure_threshold: u32,
+  /// Time to wait before probing PostHog again once the circuit is open (default: 30 seconds).
+  pub reset_timeout: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+  fn default() -> Self {
+    Self {
+      failure_threshold: 5,
+      reset_timeout: Duration::from_secs(30),
+    }
+  }
+}
+
+/// Circuit breaker state owned by the worker thread.
+pub struct CircuitBreaker {
+  config: CircuitBreakerConfig,
+  consecutive_failures: u32,
+  opened_at: Option<Instant>,
+  /// Whether a probe request was let through and its outcome is not recorded yet.
+  probing: bool,
+}
+
+impl CircuitBreaker {
+  /// Creates a closed circuit breaker.
+  pub const fn new(config: CircuitBreakerConfig) -> Self {
+    Self {
+      config,
+      consecutive_failures: 0,
+      opened_at: None,
+      probing: false,
+    }
+  }
+
+  /// Returns `true` if the circuit is open.
+  pub const fn is_open(&self) -> bool {
+    self.opened_at.is_some()
+  }
+
+  /// Returns `true` if a request may be sent, either because the circuit is closed or to probe PostHog.
+  ///
+  /// Once the circuit is open, a single probe is allowed until its outcome is recorded.
+  pub fn allows_request(&mut self) -> bool {
+    match self.opened_at {
+      None => true,
+      Some(opened_at) if !self.probing && opened_at.elapsed() >= self.config.reset_timeout => {
+        log::trace!("Probing PostHog with a single request");
+        self.probing = true;
+        true
+      }
+      Some(_) => false,
+    }
+  }
+
+  /// Records a request that reached PostHog, closing the circuit.
+  pub fn record_success(&mut self) {
+    if self.opened_at.take().is_some() {
+      log::info!("PostHog is reachable again, resuming sends");
+    }
+    self.consecutive_failures = 0;
+    self.probing = false;
+  }
+
+  /// Records a request that failed because PostHog is unavailable, opening the circuit if needed.
+  pub fn record_failure(&mut self) {
+    self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+    self.probing = false;
+    if self.opened_at.is_some() || self.consecutive_failures >= self.config.failure_threshold {
+      if self.opened_at.is_none() {
+        log::warn!(
+          "PostHog appears to be unavailable after {} consecutive failures, pausing sends for {:?}",
+          self.consecutive_failures,
+          self.config.reset_timeout
+        );
+      }
+      self.opened_at = Some(Instant::now());
+    }
+  }
+}
@@ -1,9 +1,24 @@
 use std::fmt;
-use std::sync::OnceLock;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{OnceLock, mpsc};
 use std::time::Duration;
 
-use crate::Event;
-use crate::worker::Worker;
+use crate::circuit_breaker::CircuitBreakerConfig;
+use crate::config::ConfigSnapshot;
+use crate::error::Error;
+use crate::http::{Compression, GatewayPayload};
+use crate::logging::LogLevels;
+use crate::mirror::MirrorConfig;
+use crate::once::OnceStore;
+use crate::policy::EventPolicy;
+use crate::retry::RetryConfig;
+use crate::timestamp::{TimestampGuardConfig, TimestampPrecision};
+use crate::trace::TraceContext;
+#[cfg(feature = "test-util")]
+use crate::worker::Transport;
+use crate::worker::{Worker, WorkerLifecycle};
+use crate::{Attachment, Event};
 
 /// Hook that can modify or discard events before sending.
 ///
@@ -30,7 +45,7 @@ use crate::worker::Worker;
 ///
 ///     // Return a `before_send` hook.
 ///     Box::new(move |event| {
-///       let sample_rate = match event.event.as_str() {
+///       let sample_rate = match event.name() {
 ///         "button_click" => 0.5, // Process only a half of `button_click` events.
 ///         _ => 1.0, // Process all other events.
 ///       };
@@ -42,42 +57,640 @@ use crate::worker::Worker;
 /// ```
 pub type BeforeSendFn = Box<dyn FnMut(Event) -> Option<Event> + Send + 'static>;
 
+/// Hook to modify or filter events on the thread capturing them, before they are enqueued.
+///
+/// It works like a [`BeforeSendFn`], but runs on the calling thread of capture functions instead of the worker
+/// thread. Expensive hooks, like PII scrubbing with regular expressions, then don't slow down sending,
+/// at the cost of adding their run time to each capture call. Since hooks may run on several threads at once,
+/// they must be `Fn + Send + Sync`.
+///
+/// These hooks run before the event is enriched with library/OS context and before the `before_send` hooks.
+/// Events captured with [`events::capture_raw`](crate::events::capture_raw) skip them.
+/// If any hook panics, the event is discarded and an error is logged.
+///
+/// # Example
+///
+/// ```
+/// let options = better_posthog::ClientOptions {
+///   api_key: Some("phc_your_api_key".into()),
+///   before_enqueue: vec![Box::new(|mut event| {
+///     if let Some(serde_json::Value::String(email)) = event.properties.get_mut("email") {
+///       *email = email.split('@').nth(1).unwrap_or_default().to_string();
+///     }
+///     Some(event)
+///   })],
+///   ..Default::default()
+/// };
+/// ```
+pub type BeforeEnqueueFn = Box<dyn Fn(Event) -> Option<Event> + Send + Sync + 'static>;
+
+/// Hook invoked on lifecycle transitions of the background worker.
+///
+/// The hook runs in the background worker thread. If it panics, an error is logged.
+///
+/// # Example
+///
+/// ```
+/// use better_posthog::WorkerLifecycle;
+///
+/// let options = better_posthog::ClientOptions {
+///   api_key: Some("phc_your_api_key".into()),
+///   lifecycle_hook: Some(Box::new(|lifecycle| {
+///     if lifecycle == WorkerLifecycle::Draining {
+///       println!("Sending analytics...");
+///     }
+///   })),
+///   ..Default::default()
+/// };
+/// ```
+pub type LifecycleHookFn = Box<dyn Fn(WorkerLifecycle) + Send + Sync + 'static>;
+
+/// Hook invoked with the exact body of every request sent to PostHog.
+///
+/// The body is the serialized JSON payload, compressed if the `compression` option is set.
+/// The hook runs in the background worker thread right before the request is sent. If it panics,
+/// an error is logged and the request is still sent.
+///
+/// # Example
+///
+/// ```
+/// let options = better_posthog::ClientOptions {
+///   api_key: Some("phc_your_api_key".into()),
+///   on_serialize: Some(Box::new(|body| {
+///     println!("Sending {} bytes", body.len());
+///   })),
+///   ..Default::default()
+/// };
+/// ```
+pub type SerializeHookFn = Box<dyn Fn(&[u8]) + Send + Sync + 'static>;
+
+/// Hook building the HTTP request of every delivery, for custom ingestion gateways expecting another request shape
+/// than PostHog's JSON API, like events wrapped in an envelope or form-encoded.
+///
+/// It receives the host and API key of the request, with its events, and returns the request to send instead of
+/// the default JSON one. The request is built once, and cloned for each attempt, so its body must not be streamed.
+/// It is sent with the SDK's HTTP client, configured with the request timeouts, and the status of the gateway's
+/// response is handled like PostHog's, e.g. server errors are retried with the `retry` option. The `compression`
+/// option doesn't apply, and the `on_serialize` hook receives the request body.
+///
+/// The hook runs in the background worker thread. If it fails or panics, the events are dropped and an error is
+/// logged.
+///
+/// # Example
+///
+/// ```
+/// use better_posthog::GatewayPayload;
+///
+/// let options = better_posthog::ClientOptions {
+///   api_key: Some("phc_your_api_key".into()),
+///   host: better_posthog::Host::Custom("https://gateway.example.com".into()),
+///   request_builder: Some(Box::new(|host, api_key, payload| {
+///     let events = match payload {
+///       GatewayPayload::Event(event) => std::slice::from_ref(event),
+///       GatewayPayload::Batch(events) => events,
+///       _ => return Err("raw events are not supported".into()),
+///     };
+///     let envelope = serde_json::json!({ "token": api_key.as_str(), "records": events });
+///     let request = reqwest::blocking::Client::new()
+///       .put(format!("{}/analytics/ingest", host.base_url()))
+///       .json(&envelope)
+///       .build()?;
+///     Ok(request)
+///   })),
+///   ..Default::default()
+/// };
+/// ```
+pub type RequestBuilderFn = Box<
+  dyn Fn(
+      &Host,
+      &ApiKey,
+      GatewayPayload<'_>,
+    ) -> Result<reqwest::blocking::Request, Box<dyn std::error::Error + Send + Sync>>
+    + Send
+    + Sync
+    + 'static,
+>;
+
+/// Hook invoked when PostHog rejects an API key with a `401 Unauthorized` response.
+///
+/// The hook receives the rejected key and runs in the background worker thread. If it panics, an error is logged.
+///
+/// # Example
+///
+/// ```
+/// let options = better_posthog::ClientOptions {
+///   api_key: Some("phc_your_api_key".into()),
+///   on_auth_error: Some(Box::new(|_api_key| {
+///     eprintln!("PostHog API key is invalid, check the configuration");
+///   })),
+///   disable_on_auth_error: true,
+///   ..Default::default()
+/// };
+/// ```
+pub type AuthErrorHookFn = Box<dyn Fn(&ApiKey) + Send + Sync + 'static>;
+
+/// Hook receiving the events still queued at shutdown, instead of sending them to PostHog.
+///
+/// The events are passed as they were captured, before context enrichment and `before_send` hooks, so they can be
+/// captured again by a later run. Events captured with [`events::capture_to`](crate::events::capture_to) lose their
+/// destination, and raw events that are not valid [`Event`] objects are dropped. The hook runs once,
+/// in the background worker thread, when the [`ClientGuard`](crate::ClientGuard) is dropped or
+/// [`shutdown`](crate::shutdown) is called. If it panics, an error is logged.
+///
+/// # Example
+///
+/// ```
+/// let options = better_posthog::ClientOptions {
+///   api_key: Some("phc_your_api_key".into()),
+///   on_shutdown_remaining: Some(Box::new(|events| {
+///     println!("Saving {} unsent events", events.len());
+///   })),
+///   ..Default::default()
+/// };
+/// ```
+pub type ShutdownRemainingFn = Box<dyn FnOnce(Vec<Event>) + Send + 'static>;
+
+/// Hook uploading the attachments of events captured with
+/// [`events::capture_with_attachments`](crate::events::capture_with_attachments), returning a reference URL.
+///
+/// PostHog doesn't ingest binary content, so large blobs like screenshots or log files must be stored elsewhere.
+/// The URL returned for each attachment is set as its property, so the event only carries the reference.
+///
+/// The hook runs in the background worker thread, before the event is enriched with library/OS context and passed
+/// to the `before_send` hooks, and blocks sending other events while it runs. If it fails, the attachment property
+/// is not set and a warning is logged; if it panics, an error is logged too.
+///
+/// # Example
+///
+/// ```
+/// let options = better_posthog::ClientOptions {
+///   api_key: Some("phc_your_api_key".into()),
+///   attachment_uploader: Some(Box::new(|event, attachment| {
+///     let path = format!("/var/lib/blobs/{}-{}", event.event, attachment.property);
+///     std::fs::write(&path, &attachment.data)?;
+///     Ok(format!("file://{path}"))
+///   })),
+///   ..Default::default()
+/// };
+/// ```
+pub type AttachmentUploaderFn =
+  Box<dyn FnMut(&Event, &Attachment) -> Result<String, Box<dyn std::error::Error + Send + Sync>> + Send + 'static>;
+
+/// Function providing the trace context of the current thread, attached to captured events.
+///
+/// It is called on the thread capturing each event, before the `before_enqueue` hooks, so it can read thread-local
+/// state like the current span. The returned IDs are set as the `$trace_id` and `$span_id` properties, unless the
+/// event already has them. Events captured with [`events::capture_raw`](crate::events::capture_raw) skip it.
+/// If it panics, an error is logged and the event is captured without trace context.
+///
+/// With the `tracing` feature, `TraceContext::current` provides the ID of the current `tracing` span.
+///
+/// # Example
+///
+/// ```
+/// use better_posthog::TraceContext;
+///
+/// thread_local! {
+///   static REQUEST_TRACE: std::cell::RefCell<Option<TraceContext>> = const { std::cell::RefCell::new(None) };
+/// }
+///
+/// let options = better_posthog::ClientOptions {
+///   api_key: Some("phc_your_api_key".into()),
+///   trace_context: Some(Box::new(|| REQUEST_TRACE.with_borrow(Clone::clone))),
+///   ..Default::default()
+/// };
+/// ```
+pub type TraceContextFn = Box<dyn Fn() -> Option<TraceContext> + Send + Sync + 'static>;
+
+/// Function providing anonymous distinct IDs, instead of generating random UUIDs.
+///
+/// It is called for each event without a distinct ID, possibly from multiple threads, so it should be cheap,
+/// e.g. returning an ID computed once. If it panics, an error is logged and a random UUID is used instead.
+///
+/// # Example
+///
+/// ```
+/// // Derive a stable ID from a per-install identifier, e.g. read from the app's data directory.
+/// let install_id = String::from("3f2b8c1e-install");
+///
+/// let options = better_posthog::ClientOptions {
+///   api_key: Some("phc_your_api_key".into()),
+///   anonymous_id_provider: Some(Box::new(move || install_id.clone())),
+///   ..Default::default()
+/// };
+/// ```
+pub type AnonymousIdProviderFn = Box<dyn Fn() -> String + Send + Sync + 'static>;
+
 /// Global client instance.
 pub static CLIENT: OnceLock<Client> = OnceLock::new();
 
+/// Whether [`init`](crate::init) was called, even if it didn't initialize the client.
+pub static INIT_CALLED: AtomicBool = AtomicBool::new(false);
+
+/// Returns the client to capture events with.
+///
+/// Logs a warning the first time events are captured before [`init`](crate::init) is called, since they are dropped.
+pub fn capture_client() -> Option<&'static Client> {
+  static WARNED: AtomicBool = AtomicBool::new(false);
+
+  let client = CLIENT.get();
+  if client.is_none() && !INIT_CALLED.load(Ordering::Relaxed) && !WARNED.swap(true, Ordering::Relaxed) {
+    log::warn!("PostHog events captured before `better_posthog::init` was called are dropped");
+  }
+  client
+}
+
 /// Internal client state holding the worker.
 pub struct Client {
   pub worker: Worker,
+  pub config: ConfigSnapshot,
+  pub anonymous_id_version: AnonymousIdVersion,
+  pub anonymous_id_provider: Option<AnonymousIdProviderFn>,
+  pub timestamp_precision: TimestampPrecision,
+  pub once_store: Option<Box<dyn OnceStore>>,
 }
 
 impl Client {
   /// Creates a new client from the given configuration.
-  pub fn new(options: ClientOptions) -> Self {
+  pub fn new(mut options: ClientOptions) -> Self {
+    let anonymous_id_version = options.anonymous_id_version;
+    let anonymous_id_provider = options.anonymous_id_provider.take();
+    let timestamp_precision = options.timestamp_precision;
+    let once_store = options.once_store.take();
+    let config = ConfigSnapshot::new(&options);
     let worker = Worker::new(options);
-    Self { worker }
+    Self {
+      worker,
+      config,
+      anonymous_id_version,
+      anonymous_id_provider,
+      timestamp_precision,
+      once_store,
+    }
+  }
+
+  /// Creates a new client that delivers events with the given transport.
+  #[cfg(feature = "test-util")]
+  pub fn with_transport(mut options: ClientOptions, transport: Transport) -> Self {
+    let anonymous_id_version = options.anonymous_id_version;
+    let anonymous_id_provider = options.anonymous_id_provider.take();
+    let timestamp_precision = options.timestamp_precision;
+    let once_store = options.once_store.take();
+    let config = ConfigSnapshot::new(&options);
+    let worker = Worker::with_transport(options, transport);
+    Self {
+      worker,
+      config,
+      anonymous_id_version,
+      anonymous_id_provider,
+      timestamp_precision,
+      once_store,
+    }
+  }
+}
+
+/// Generates an anonymous distinct ID with the provider or the UUID version configured at initialization.
+pub fn anonymous_id() -> String {
+  let Some(client) = CLIENT.get() else {
+    return AnonymousIdVersion::default().generate();
+  };
+  if let Some(provider) = &client.anonymous_id_provider {
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(provider)) {
+      Ok(id) => return id,
+      Err(_) => log::error!("Panic in anonymous_id_provider, generating a random ID"),
+    }
   }
+  client.anonymous_id_version.generate()
+}
+
+/// Returns the precision of generated timestamps configured at initialization.
+pub fn timestamp_precision() -> TimestampPrecision {
+  CLIENT
+    .get()
+    .map_or_else(TimestampPrecision::default, |client| client.timestamp_precision)
 }
 
 /// Configuration for the PostHog client.
+#[allow(clippy::struct_excessive_bools)] // Independent on/off options, not states.
 pub struct ClientOptions {
   /// The PostHog API key. If `None`, the client will not be initialized.
   pub api_key: Option<ApiKey>,
   /// The target PostHog host.
   pub host: Host,
+  /// Endpoint receiving single events (default: [`CaptureEndpoint::Event`]).
+  ///
+  /// Set it to [`CaptureEndpoint::Capture`] for self-hosted instances or proxies that only expose `/capture/`.
+  pub capture_endpoint: CaptureEndpoint,
   /// Timeout for graceful shutdown (default: 2 seconds).
   pub shutdown_timeout: Duration,
+  /// What to do with events captured while the client is shutting down (default: [`ShutdownCapturePolicy::Reject`]).
+  pub shutdown_capture_policy: ShutdownCapturePolicy,
+  /// Policies dropping or sampling events by name, checked in order (default: none).
+  ///
+  /// Each pattern matches the event names it equals, with `*` matching any sequence of characters, like `debug_*`.
+  /// The first matching pattern decides, and events matching none are captured. Policies apply on the thread
+  /// capturing each event, before the `before_enqueue` and `before_send` hooks, and discarded events are counted
+  /// as filtered. Raw events captured with [`events::capture_raw`](crate::events::capture_raw) are not matched.
+  pub event_policies: Vec<(String, EventPolicy)>,
   /// Hooks to modify or filter events before sending.
   pub before_send: Vec<BeforeSendFn>,
+  /// Hooks to modify or filter events on the thread capturing them, before they are enqueued.
+  pub before_enqueue: Vec<BeforeEnqueueFn>,
+  /// Hook invoked on lifecycle transitions of the background worker.
+  pub lifecycle_hook: Option<LifecycleHookFn>,
+  /// Circuit breaker pausing sends while PostHog is unavailable (default: disabled).
+  ///
+  /// While the circuit is open, events are dropped without being sent and counted as failed.
+  /// If `None`, sending is always attempted.
+  pub circuit_breaker: Option<CircuitBreakerConfig>,
+  /// Channel receiving a copy of every outgoing event, for custom fan-out.
+  ///
+  /// Events are mirrored after context enrichment and `before_send` hooks, right before delivery,
+  /// regardless of the delivery outcome. Raw events captured with [`events::capture_raw`](crate::events::capture_raw)
+  /// are not mirrored. Keep the receiver drained, since unread events accumulate in memory.
+  ///
+  /// # Example
+  ///
+  /// ```no_run
+  /// let (tap, events) = std::sync::mpsc::channel();
+  /// let _guard = better_posthog::init(better_posthog::ClientOptions {
+  ///   api_key: Some("phc_your_api_key".into()),
+  ///   tap: Some(tap),
+  ///   ..Default::default()
+  /// });
+  ///
+  /// std::thread::spawn(move || {
+  ///   for event in events {
+  ///     println!("Sent {}", event.event);
+  ///   }
+  /// });
+  /// ```
+  pub tap: Option<mpsc::Sender<Event>>,
+  /// Secondary endpoints receiving a copy of every event, each with its own queue and failure handling.
+  ///
+  /// Raw events captured with [`events::capture_raw`](crate::events::capture_raw) are mirrored too, but events
+  /// captured with [`events::capture_to`](crate::events::capture_to) are not. See [`MirrorConfig`].
+  pub mirrors: Vec<MirrorConfig>,
+  /// Whether to strip properties with a `null` value before sending (default: `false`).
+  ///
+  /// Keep it disabled if you rely on `null` values to unset PostHog properties.
+  /// It also applies to the `properties` object of raw events captured with
+  /// [`events::capture_raw`](crate::events::capture_raw).
+  pub strip_null_properties: bool,
+  /// Whether to merge the person property updates of events sent together (default: `false`).
+  ///
+  /// PostHog applies `$set` and `$unset` updates with last-write-wins, and `$set_once` updates with
+  /// first-write-wins, in the order it processes events, which may differ from the capture order within a batch
+  /// or across concurrent requests. When enabled, only the last `$set` or `$unset` and the first `$set_once` of
+  /// each person property, in capture order, are kept among the events sent together, so the resulting person
+  /// state doesn't depend on the processing order. Updates overridden this way are removed from the other events.
+  /// Events sent to a different destination with [`events::capture_to`](crate::events::capture_to) are not merged.
+  pub merge_person_properties: bool,
+  /// Property keys that are never sent to PostHog, e.g. to prevent leaking PII.
+  ///
+  /// Denied properties are stripped from every event after `before_send` hooks, right before delivery,
+  /// so hooks can't reintroduce them. They are also stripped from the `properties` object of raw events captured
+  /// with [`events::capture_raw`](crate::events::capture_raw).
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// let options = better_posthog::ClientOptions {
+  ///   api_key: Some("phc_your_api_key".into()),
+  ///   property_deny_list: vec!["email".into(), "$ip".into()],
+  ///   ..Default::default()
+  /// };
+  /// ```
+  pub property_deny_list: Vec<String>,
+  /// Compression applied to request bodies (default: [`Compression::None`]).
+  pub compression: Compression,
+  /// Hook invoked with the exact body of every request sent to PostHog, e.g. for audit logging.
+  pub on_serialize: Option<SerializeHookFn>,
+  /// Hook building the HTTP requests for a custom ingestion gateway, instead of PostHog's JSON API.
+  pub request_builder: Option<RequestBuilderFn>,
+  /// Hook uploading event attachments, replacing them with reference URL properties (default: `None`).
+  ///
+  /// Without it, the attachments of events captured with
+  /// [`events::capture_with_attachments`](crate::events::capture_with_attachments) are dropped.
+  pub attachment_uploader: Option<AttachmentUploaderFn>,
+  /// Path of a file used to spool events until they are delivered (default: disabled).
+  ///
+  /// Events are appended to the spool when they are enqueued, and removed once their delivery is handled or they
+  /// are taken from the queue with [`events::drain`](crate::events::drain). If the process crashes, the spooled
+  /// events are resent at the next initialization, so the ones interrupted mid-delivery may be delivered twice.
+  /// Events sent to a different destination with [`events::capture_to`](crate::events::capture_to), events with
+  /// attachments, blocking batches, and raw events are not spooled.
+  pub spool_path: Option<PathBuf>,
+  /// Store of the keys of the events already captured with [`events::capture_once`](crate::events::capture_once),
+  /// like a [`FileOnceStore`](crate::FileOnceStore).
+  ///
+  /// If `None`, `capture_once` doesn't capture anything.
+  pub once_store: Option<Box<dyn OnceStore>>,
+  /// Hook invoked when PostHog rejects an API key.
+  pub on_auth_error: Option<AuthErrorHookFn>,
+  /// Whether to stop sending events once PostHog rejects the configured API key (default: `false`).
+  ///
+  /// Once disabled, events are dropped without being sent until the application restarts.
+  /// Keep it disabled if the key may become valid later, e.g. while a project is being set up.
+  pub disable_on_auth_error: bool,
+  /// Distinct ID of the events sent without one, instead of an anonymous ID (default: `None`).
+  ///
+  /// This suits services and command-line tools that always act as the same identity, like a service account.
+  /// Events with a distinct ID, set explicitly, by [`events::with_distinct_id`](crate::events::with_distinct_id),
+  /// or by a hook, keep it. Events created with [`Event::new_anonymous`] keep their anonymous ID.
+  pub default_distinct_id: Option<String>,
+  /// UUID version of generated anonymous distinct IDs (default: [`AnonymousIdVersion::V7`]).
+  pub anonymous_id_version: AnonymousIdVersion,
+  /// Function providing anonymous distinct IDs, replacing random UUIDs, e.g. for per-install IDs.
+  pub anonymous_id_provider: Option<AnonymousIdProviderFn>,
+  /// Whether events with an anonymous distinct ID create person profiles (default: `false`).
+  ///
+  /// By default, [anonymous](Event::is_anonymous) events are sent with `$process_person_profile` set to `false`,
+  /// unless they set it themselves, following PostHog's recommendation to only process identified persons.
+  pub process_anonymous_persons: bool,
+  /// Library reported in the `$lib` and `$lib_version` properties of events instead of this crate (default: `None`).
+  ///
+  /// Wrapper SDKs can set it to their own identity; this crate's name and version are then reported
+  /// in the `$lib_core` and `$lib_core_version` properties.
+  pub library: Option<Library>,
+  /// Deployment environment of the application, like `"production"`, set as the `environment` property of events
+  /// (default: `None`).
+  pub environment: Option<String>,
+  /// Release of the application, like a version or commit hash, set as the `release` property of events
+  /// (default: `None`).
+  pub release: Option<String>,
+  /// Fractional second digits of timestamps generated by the SDK (default: [`TimestampPrecision::Millis`]).
+  pub timestamp_precision: TimestampPrecision,
+  /// Guard against event timestamps far in the future or in the past, e.g. set by misconfigured clocks
+  /// (default: disabled).
+  ///
+  /// Timestamps are checked when events are enriched, before the `before_send` hooks.
+  /// Raw events captured with [`events::capture_raw`](crate::events::capture_raw) are sent as is.
+  pub timestamp_guard: Option<TimestampGuardConfig>,
+  /// Whether events are imported historical data (default: `false`).
+  ///
+  /// All events are then sent through the batch endpoint with PostHog's `historical_migration` flag,
+  /// which routes them to a separate ingestion pipeline so they don't delay live events,
+  /// and the `timestamp_guard` accepts timestamps of any age.
+  pub historical_migration: bool,
+  /// Maximum estimated size of a batch request body in bytes, before compression (default: unlimited).
+  ///
+  /// Batches captured with [`events::batch`](crate::events::batch) that exceed it are split into multiple requests,
+  /// keeping request sizes predictable and below PostHog's payload limits.
+  pub max_batch_bytes: Option<usize>,
+  /// Maximum estimated size of a single event in bytes, beyond which the `oversized_event_policy` applies
+  /// (default: unlimited).
+  ///
+  /// PostHog rejects single event requests larger than about 1 MB. Events captured in a batch are not checked,
+  /// see the `max_batch_bytes` option.
+  pub max_event_bytes: Option<usize>,
+  /// What to do with single events exceeding `max_event_bytes` (default: [`OversizedEventPolicy::Drop`]).
+  pub oversized_event_policy: OversizedEventPolicy,
+  /// Retries of requests that failed because PostHog is unavailable (default: disabled).
+  pub retry: Option<RetryConfig>,
+  /// Number of recent `$insert_id`s remembered to drop duplicate events before sending them (default: disabled).
+  ///
+  /// Events with the same `$insert_id` as one of the most recent events are dropped and counted in
+  /// [`Stats::events_deduplicated`](crate::Stats::events_deduplicated), which saves the requests PostHog would
+  /// deduplicate anyway, e.g. for producers that may enqueue the same event twice. Events without an `$insert_id`
+  /// are always sent.
+  pub dedup_window: Option<usize>,
+  /// Log levels of recurring operational messages, like send failures or dropped events.
+  pub log_levels: LogLevels,
+  /// Timeout of each request to PostHog, from connecting until the response is received (default: 30 seconds).
+  pub request_timeout: Duration,
+  /// Timeout for establishing a connection to PostHog, including DNS resolution (default: only bounded
+  /// by `request_timeout`).
+  ///
+  /// A short connect timeout keeps the worker from stalling on unreachable networks while still allowing
+  /// slow uploads of large batches.
+  pub connect_timeout: Option<Duration>,
+  /// Maximum number of requests to PostHog in flight at once (default: `1`).
+  ///
+  /// With more than one, the worker takes up to this many tasks from the queue at a time and sends their requests
+  /// concurrently, improving throughput against high-latency PostHog instances. Events are then no longer
+  /// guaranteed to arrive in the order they were captured. Values below `1` are treated as `1`.
+  pub max_concurrent_requests: usize,
+  /// Time without events after which the worker thread exits, to be respawned on the next capture (default: never).
+  ///
+  /// This saves resources in mostly idle applications, like desktop apps, at the cost of spawning a thread
+  /// on the next capture. The HTTP connection pool and hooks are kept.
+  pub idle_timeout: Option<Duration>,
+  /// Whether to respawn the worker thread on the next capture if it panics (default: `false`).
+  ///
+  /// The events being processed when it panicked are lost. Otherwise, the client is marked as unhealthy,
+  /// see [`Stats::worker_gone`](crate::Stats::worker_gone), and new events are dropped with
+  /// [`Error::WorkerGone`](crate::Error::WorkerGone) instead of filling up the queue.
+  pub respawn_on_panic: bool,
+  /// Event captured at shutdown, before the remaining events are sent, e.g. a `$pageleave` event (default: `None`).
+  ///
+  /// It gets a `$session_duration` property with the number of seconds since initialization, to measure engagement
+  /// time without manual bookkeeping. It is captured when the [`ClientGuard`](crate::ClientGuard) is dropped,
+  /// or by [`shutdown`](crate::shutdown).
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// use better_posthog::Event;
+  ///
+  /// let options = better_posthog::ClientOptions {
+  ///   api_key: Some("phc_your_api_key".into()),
+  ///   session_end_event: Some(Event::new("$pageleave", "user_123")),
+  ///   ..Default::default()
+  /// };
+  /// ```
+  pub session_end_event: Option<Event>,
+  /// Hook receiving the events still queued at shutdown, instead of sending them to PostHog.
+  pub on_shutdown_remaining: Option<ShutdownRemainingFn>,
+  /// Whether to set the timestamp of events without one to the time they are captured (default: `false`).
+  ///
+  /// Otherwise, PostHog uses the time it receives them, which may be later if they were queued, batched, or retried.
+  /// Raw events captured with [`events::capture_raw`](crate::events::capture_raw) are sent as is.
+  pub auto_timestamp: bool,
+  /// Whether to drop events whose timestamp is not a valid RFC 3339 date-time (default: `false`).
+  ///
+  /// Such events are rejected by PostHog, so a warning is logged when they are captured either way.
+  /// Dropped events are counted as filtered. Raw events captured with
+  /// [`events::capture_raw`](crate::events::capture_raw) are not checked.
+  pub strict_timestamps: bool,
+  /// Function providing the trace context attached to captured events as `$trace_id` and `$span_id` (default: `None`).
+  pub trace_context: Option<TraceContextFn>,
+  /// Path of a Unix domain socket that all requests are sent through instead of TCP (default: disabled).
+  ///
+  /// Requests still target the host's URL, e.g. `Host::Custom("http://localhost".into())`, which sets the path and
+  /// `Host` header, but its domain is not resolved. This is useful to reach a local ingestion proxy in environments
+  /// that forbid local TCP. Applies to events captured with [`events::capture_to`](crate::events::capture_to) too.
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// let options = better_posthog::ClientOptions {
+  ///   api_key: Some("phc_your_api_key".into()),
+  ///   host: better_posthog::Host::Custom("http://localhost".into()),
+  ///   unix_socket: Some("/run/posthog-proxy.sock".into()),
+  ///   ..Default::default()
+  /// };
+  /// ```
+  #[cfg(unix)]
+  pub unix_socket: Option<PathBuf>,
 }
 
 impl fmt::Debug for ClientOptions {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    f.debug_struct("ClientOptions")
+    let mut debug = f.debug_struct("ClientOptions");
+    debug
       .field("api_key", &self.api_key)
       .field("host", &self.host)
+      .field("capture_endpoint", &self.capture_endpoint)
       .field("shutdown_timeout", &self.shutdown_timeout)
+      .field("shutdown_capture_policy", &self.shutdown_capture_policy)
+      .field("event_policies", &self.event_policies)
       .field("before_send", &format!("[{} hooks]", self.before_send.len()))
-      .finish()
+      .field("before_enqueue", &format!("[{} hooks]", self.before_enqueue.len()))
+      .field("lifecycle_hook", &self.lifecycle_hook.is_some())
+      .field("circuit_breaker", &self.circuit_breaker)
+      .field("tap", &self.tap.is_some())
+      .field("mirrors", &self.mirrors)
+      .field("strip_null_properties", &self.strip_null_properties)
+      .field("merge_person_properties", &self.merge_person_properties)
+      .field("property_deny_list", &self.property_deny_list)
+      .field("compression", &self.compression)
+      .field("on_serialize", &self.on_serialize.is_some())
+      .field("request_builder", &self.request_builder.is_some())
+      .field("attachment_uploader", &self.attachment_uploader.is_some())
+      .field("spool_path", &self.spool_path)
+      .field("once_store", &self.once_store.is_some())
+      .field("on_auth_error", &self.on_auth_error.is_some())
+      .field("disable_on_auth_error", &self.disable_on_auth_error)
+      .field("default_distinct_id", &self.default_distinct_id)
+      .field("anonymous_id_version", &self.anonymous_id_version)
+      .field("anonymous_id_provider", &self.anonymous_id_provider.is_some())
+      .field("process_anonymous_persons", &self.process_anonymous_persons)
+      .field("library", &self.library)
+      .field("environment", &self.environment)
+      .field("release", &self.release)
+      .field("timestamp_precision", &self.timestamp_precision)
+      .field("timestamp_guard", &self.timestamp_guard)
+      .field("historical_migration", &self.historical_migration)
+      .field("max_batch_bytes", &self.max_batch_bytes)
+      .field("max_event_bytes", &self.max_event_bytes)
+      .field("oversized_event_policy", &self.oversized_event_policy)
+      .field("retry", &self.retry)
+      .field("dedup_window", &self.dedup_window)
+      .field("log_levels", &self.log_levels)
+      .field("request_timeout", &self.request_timeout)
+      .field("connect_timeout", &self.connect_timeout)
+      .field("max_concurrent_requests", &self.max_concurrent_requests)
+      .field("idle_timeout", &self.idle_timeout)
+      .field("respawn_on_panic", &self.respawn_on_panic)
+      .field("session_end_event", &self.session_end_event)
+      .field("on_shutdown_remaining", &self.on_shutdown_remaining.is_some())
+      .field("auto_timestamp", &self.auto_timestamp)
+      .field("strict_timestamps", &self.strict_timestamps)
+      .field("trace_context", &self.trace_context.is_some());
+    #[cfg(unix)]
+    debug.field("unix_socket", &self.unix_socket);
+    debug.finish()
   }
 }
 
@@ -86,8 +699,55 @@ impl Default for ClientOptions {
     Self {
       api_key: None,
       host: Host::default(),
+      capture_endpoint: CaptureEndpoint::Event,
       shutdown_timeout: Duration::from_secs(2),
+      shutdown_capture_policy: ShutdownCapturePolicy::Reject,
+      event_policies: Vec::new(),
       before_send: Vec::new(),
+      before_enqueue: Vec::new(),
+      lifecycle_hook: None,
+      circuit_breaker: None,
+      tap: None,
+      mirrors: Vec::new(),
+      strip_null_properties: false,
+      merge_person_properties: false,
+      property_deny_list: Vec::new(),
+      compression: Compression::None,
+      on_serialize: None,
+      request_builder: None,
+      attachment_uploader: None,
+      spool_path: None,
+      once_store: None,
+      on_auth_error: None,
+      disable_on_auth_error: false,
+      default_distinct_id: None,
+      anonymous_id_version: AnonymousIdVersion::V7,
+      anonymous_id_provider: None,
+      process_anonymous_persons: false,
+      library: None,
+      environment: None,
+      release: None,
+      timestamp_precision: TimestampPrecision::Millis,
+      timestamp_guard: None,
+      historical_migration: false,
+      max_batch_bytes: None,
+      max_event_bytes: None,
+      oversized_event_policy: OversizedEventPolicy::Drop,
+      retry: None,
+      dedup_window: None,
+      log_levels: LogLevels::default(),
+      request_timeout: Duration::from_secs(30),
+      connect_timeout: None,
+      max_concurrent_requests: 1,
+      idle_timeout: None,
+      respawn_on_panic: false,
+      session_end_event: None,
+      on_shutdown_remaining: None,
+      auto_timestamp: false,
+      strict_timestamps: false,
+      trace_context: None,
+      #[cfg(unix)]
+      unix_socket: None,
     }
   }
 }
@@ -100,6 +760,86 @@ impl ClientOptions {
       ..Default::default()
     }
   }
+
+  /// Checks the options for consistency, like a valid custom host URL and non-zero timeouts and size limits.
+  ///
+  /// This runs at initialization: [`init`](crate::init) logs the error, while [`try_init`](crate::try_init)
+  /// returns it.
+  ///
+  /// # Errors
+  ///
+  /// Returns [`Error::Config`] describing the first invalid option.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use better_posthog::{ClientOptions, Host};
+  ///
+  /// let options = ClientOptions {
+  ///   host: Host::Custom("posthog.example.com".into()),
+  ///   ..ClientOptions::new("phc_your_api_key")
+  /// };
+  /// assert!(options.validate().is_err());
+  /// ```
+  pub fn validate(&self) -> Result<(), Error> {
+    let invalid = |message: String| Err(Error::Config(message));
+
+    if let Host::Custom(url) = &self.host {
+      match reqwest::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {}
+        Ok(parsed) => return invalid(format!("host `{url}` must use http or https, not {}", parsed.scheme())),
+        Err(e) => return invalid(format!("host `{url}` is not a valid URL: {e}")),
+      }
+    }
+    if self.request_timeout.is_zero() {
+      return invalid("request_timeout must not be zero".into());
+    }
+    if self.connect_timeout.is_some_and(|timeout| timeout.is_zero()) {
+      return invalid("connect_timeout must not be zero".into());
+    }
+    if self.idle_timeout.is_some_and(|timeout| timeout.is_zero()) {
+      return invalid("idle_timeout must not be zero".into());
+    }
+    if self.max_batch_bytes == Some(0) {
+      return invalid("max_batch_bytes must not be zero".into());
+    }
+    if self.max_event_bytes == Some(0) {
+      return invalid("max_event_bytes must not be zero".into());
+    }
+    if self.default_distinct_id.as_deref() == Some("") {
+      return invalid("default_distinct_id must not be empty".into());
+    }
+    if self.dedup_window == Some(0) {
+      return invalid("dedup_window must not be zero".into());
+    }
+    if let Some(retry) = &self.retry
+      && retry.initial_backoff > retry.max_backoff
+    {
+      return invalid("retry initial_backoff must not exceed max_backoff".into());
+    }
+    if self
+      .circuit_breaker
+      .as_ref()
+      .is_some_and(|circuit_breaker| circuit_breaker.failure_threshold == 0)
+    {
+      return invalid("circuit_breaker failure_threshold must not be zero".into());
+    }
+    for (pattern, policy) in &self.event_policies {
+      if let EventPolicy::Sample(rate) = policy
+        && !(0.0..=1.0).contains(rate)
+      {
+        return invalid(format!(
+          "sample rate of event policy `{pattern}` must be between 0 and 1"
+        ));
+      }
+    }
+    for (index, mirror) in self.mirrors.iter().enumerate() {
+      if let Err(Error::Config(message)) = mirror.options(self).validate() {
+        return invalid(format!("mirror {index}: {message}"));
+      }
+    }
+    Ok(())
+  }
 }
 
 impl<T: Into<ApiKey>> From<T> for ClientOptions {
@@ -115,8 +855,161 @@ impl<T: Into<ApiKey>> From<(T, Self)> for ClientOptions {
   }
 }
 
+/// Name and version of a library wrapping this crate, reported as the library that captured events.
+///
+/// # Examples
+///
+/// ```
+/// use better_posthog::Library;
+///
+/// let options = better_posthog::ClientOptions {
+///   api_key: Some("phc_your_api_key".into()),
+///   library: Some(Library::new("my-posthog-wrapper", "1.2.0")),
+///   ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Library {
+  /// Name of the library, reported in the `$lib` property.
+  pub name: String,
+  /// Version of the library, reported in the `$lib_version` property.
+  ///
+  /// If it is a valid semantic version, its components are reported in the `$lib_version_major`,
+  /// `$lib_version_minor`, and `$lib_version_patch` properties too.
+  pub version: String,
+}
+
+impl Library {
+  /// Creates a library identity from its name and version.
+  pub fn new(name: impl Into<String>, version: impl Into<String>) -> Self {
+    Self {
+      name: name.into(),
+      version: version.into(),
+    }
+  }
+}
+
+/// UUID version used to generate anonymous distinct IDs.
+///
+/// # Examples
+///
+/// ```
+/// use better_posthog::AnonymousIdVersion;
+///
+/// let options = better_posthog::ClientOptions {
+///   api_key: Some("phc_your_api_key".into()),
+///   anonymous_id_version: AnonymousIdVersion::V4,
+///   ..Default::default()
+/// };
+///
+/// // Correlate anonymous events with the machine that captured them.
+/// let options = better_posthog::ClientOptions {
+///   api_key: Some("phc_your_api_key".into()),
+///   anonymous_id_version: AnonymousIdVersion::V7WithNode([0x02, 0x42, 0xac, 0x11, 0x00, 0x02]),
+///   ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AnonymousIdVersion {
+  /// Time-ordered UUID v7, which reveals approximately when the ID was generated.
+  #[default]
+  V7,
+  /// Random UUID v4, which doesn't reveal when the ID was generated.
+  V4,
+  /// Time-ordered UUID v7 whose last 6 bytes are the given node ID, like a machine ID, instead of random bits.
+  ///
+  /// The node ID is the last 12 hexadecimal digits of the generated IDs, so anonymous events can be correlated
+  /// with the machine that captured them without identifying the user. IDs generated with the same node ID
+  /// in the same millisecond only differ by 26 random bits, and are not ordered within that millisecond.
+  V7WithNode([u8; 6]),
+}
+
+impl AnonymousIdVersion {
+  /// Generates a new ID with this UUID version.
+  pub(crate) fn generate(self) -> String {
+    match self {
+      Self::V7 => uuid::Uuid::now_v7().to_string(),
+      Self::V4 => uuid::Uuid::new_v4().to_string(),
+      Self::V7WithNode(node) => {
+        // The monotonic counter of `Uuid::now_v7` spans the bits replaced by the node, so use random bits instead.
+        let millis = std::time::SystemTime::now()
+          .duration_since(std::time::UNIX_EPOCH)
+          .map_or(0, |elapsed| u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX));
+        let mut random_bytes = [0; 10];
+        fastrand::fill(&mut random_bytes[..4]);
+        random_bytes[4..].copy_from_slice(&node);
+        uuid::Builder::from_unix_timestamp_millis(millis, &random_bytes)
+          .into_uuid()
+          .to_string()
+      }
+    }
+  }
+}
+
+/// What to do with single events exceeding the `max_event_bytes` option.
+///
+/// Oversized events are counted in [`Stats::events_oversized`](crate::Stats::events_oversized) whatever the policy.
+///
+/// # Examples
+///
+/// ```
+/// use better_posthog::OversizedEventPolicy;
+///
+/// let options = better_posthog::ClientOptions {
+///   api_key: Some("phc_your_api_key".into()),
+///   max_event_bytes: Some(1_000_000),
+///   oversized_event_policy: OversizedEventPolicy::StripLargeProperties,
+///   ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum OversizedEventPolicy {
+  /// Drop the event, logging a warning with its name and size.
+  #[default]
+  Drop,
+  /// Remove the largest properties until the event fits, logging a warning with their keys.
+  StripLargeProperties,
+  /// Send the event through the batch endpoint, which accepts larger payloads.
+  SendInBatch,
+}
+
+/// What to do with events captured while the client is shutting down, from the moment the [`ClientGuard`] is
+/// dropped or [`shutdown`] is called until the pending events are sent or the timeout elapses.
+///
+/// Events captured once the shutdown completed are always ignored.
+///
+/// # Examples
+///
+/// ```
+/// use better_posthog::ShutdownCapturePolicy;
+///
+/// let options = better_posthog::ClientOptions {
+///   api_key: Some("phc_your_api_key".into()),
+///   shutdown_capture_policy: ShutdownCapturePolicy::Include,
+///   ..Default::default()
+/// };
+/// ```
+///
+/// [`ClientGuard`]: crate::ClientGuard
+/// [`shutdown`]: crate::shutdown
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ShutdownCapturePolicy {
+  /// Drop the events, counting them in [`Stats::events_dropped`](crate::Stats::events_dropped) and logging them
+  /// at the `queue_drop` level, so the shutdown only waits for the events captured before it.
+  #[default]
+  Reject,
+  /// Send the events along with the pending ones, as long as the shutdown timeout didn't elapse.
+  ///
+  /// Events captured right as the last pending events are sent, or after the `on_shutdown_remaining` hook was
+  /// called, are dropped and counted like with [`ShutdownCapturePolicy::Reject`].
+  Include,
+}
+
 /// PostHog API key newtype.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Deserialize)]
 pub struct ApiKey(String);
 
 impl ApiKey {
@@ -125,6 +1018,30 @@ impl ApiKey {
   pub fn as_str(&self) -> &str {
     &self.0
   }
+
+  /// Returns the API key with everything but its prefix redacted, like `phc_***`, for logging.
+  pub(crate) fn redacted(&self) -> String {
+    let prefix = self.0.find('_').map_or("", |index| &self.0[..=index]);
+    format!("{prefix}***")
+  }
+
+  /// Returns a description of a likely misconfiguration if the key doesn't look like a PostHog project API key.
+  ///
+  /// Self-hosted instances may use a different format, so this is only a hint.
+  pub(crate) fn format_issue(&self) -> Option<&'static str> {
+    let key = self.0.as_str();
+    if key.is_empty() {
+      Some("the API key is empty")
+    } else if key.trim() != key {
+      Some("the API key contains leading or trailing whitespace")
+    } else if key.starts_with("phx_") {
+      Some("the API key looks like a personal API key, but a project API key (`phc_...`) is expected")
+    } else if !key.starts_with("phc_") {
+      Some("the API key doesn't start with `phc_`")
+    } else {
+      None
+    }
+  }
 }
 
 impl From<&str> for ApiKey {
@@ -152,6 +1069,52 @@ pub enum Host {
 }
 
 impl Host {
+  /// Returns the host of the PostHog Cloud instance in the given region.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use better_posthog::{Host, Region};
+  ///
+  /// assert_eq!(Host::from_project_region(Region::Eu).base_url(), "https://eu.i.posthog.com");
+  /// ```
+  #[must_use]
+  pub const fn from_project_region(region: Region) -> Self {
+    match region {
+      Region::Us => Self::US,
+      Region::Eu => Self::EU,
+    }
+  }
+
+  /// Returns a host for a local PostHog instance at <http://localhost:8000>,
+  /// the default of PostHog's docker-compose setup.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use better_posthog::Host;
+  ///
+  /// assert_eq!(Host::local().capture_url(), "http://localhost:8000/i/v0/e/");
+  /// ```
+  #[must_use]
+  pub fn local() -> Self {
+    Self::local_with_port(8000)
+  }
+
+  /// Returns a host for a local PostHog instance listening on the given port over plain HTTP.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use better_posthog::Host;
+  ///
+  /// assert_eq!(Host::local_with_port(8010).batch_url(), "http://localhost:8010/batch/");
+  /// ```
+  #[must_use]
+  pub fn local_with_port(port: u16) -> Self {
+    Self::Custom(format!("http://localhost:{port}"))
+  }
+
   /// Returns the base URL for this host.
   #[must_use]
   pub const fn base_url(&self) -> &str {
@@ -165,7 +1128,21 @@ impl Host {
   /// Returns the single event capture endpoint URL.
   #[must_use]
   pub fn capture_url(&self) -> String {
-    format!("{}/i/v0/e/", self.base_url())
+    self.capture_url_for(CaptureEndpoint::Event)
+  }
+
+  /// Returns the URL of the given single event capture endpoint.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use better_posthog::{CaptureEndpoint, Host};
+  ///
+  /// assert_eq!(Host::local().capture_url_for(CaptureEndpoint::Capture), "http://localhost:8000/capture/");
+  /// ```
+  #[must_use]
+  pub fn capture_url_for(&self, endpoint: CaptureEndpoint) -> String {
+    format!("{}{}", self.base_url(), endpoint.path())
   }
 
   /// Returns the batch event endpoint URL.
@@ -174,3 +1151,90 @@ impl Host {
     format!("{}/batch/", self.base_url())
   }
 }
+
+impl<'de> serde::Deserialize<'de> for Host {
+  /// Deserializes a host from a region code, `"us"` or `"eu"`, or the base URL of a custom instance.
+  fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let host = String::deserialize(deserializer)?;
+    Ok(match host.to_ascii_lowercase().as_str() {
+      "us" => Self::US,
+      "eu" => Self::EU,
+      _ => Self::Custom(host),
+    })
+  }
+}
+
+impl From<Region> for Host {
+  fn from(region: Region) -> Self {
+    Self::from_project_region(region)
+  }
+}
+
+/// Endpoint receiving single events, see the `capture_endpoint` option.
+///
+/// Both accept the same payload; which ones are available depends on the PostHog version and the proxies in front of it.
+///
+/// # Examples
+///
+/// ```
+/// use better_posthog::{CaptureEndpoint, Host};
+///
+/// let options = better_posthog::ClientOptions {
+///   api_key: Some("phc_your_api_key".into()),
+///   host: Host::Custom("https://posthog.example.com".into()),
+///   capture_endpoint: CaptureEndpoint::Capture,
+///   ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CaptureEndpoint {
+  /// The `/i/v0/e/` endpoint, used by PostHog Cloud.
+  #[default]
+  Event,
+  /// The `/capture/` endpoint, exposed by older versions and some self-hosted setups.
+  Capture,
+}
+
+impl CaptureEndpoint {
+  /// Returns the path of the endpoint, relative to the host's base URL.
+  #[must_use]
+  pub const fn path(self) -> &'static str {
+    match self {
+      Self::Event => "/i/v0/e/",
+      Self::Capture => "/capture/",
+    }
+  }
+}
+
+/// Region of a PostHog Cloud project, e.g. read from a configuration file.
+///
+/// It deserializes from its lowercase code, `"us"` or `"eu"`, and converts into a [`Host`].
+///
+/// # Examples
+///
+/// ```
+/// use better_posthog::Region;
+///
+/// #[derive(serde::Deserialize)]
+/// struct Config {
+///   posthog_region: Region,
+/// }
+///
+/// let config: Config = serde_json::from_str(r#"{ "posthog_region": "eu" }"#).unwrap();
+/// let options = better_posthog::ClientOptions {
+///   api_key: Some("phc_your_api_key".into()),
+///   host: config.posthog_region.into(),
+///   ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum Region {
+  /// United States (<https://us.i.posthog.com>).
+  #[default]
+  Us,
+  /// European Union (<https://eu.i.posthog.com>).
+  Eu,
+}
@@ -0,0 +1,148 @@
+//! Deserializable subset of the client configuration, and snapshots of the effective one.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Deserializer, de};
+
+use crate::client::{ApiKey, ClientOptions, Host};
+use crate::http::Compression;
+use crate::worker::QUEUE_CAPACITY;
+
+/// Subset of [`ClientOptions`] that can be read from a configuration file, like TOML, YAML, or JSON.
+///
+/// Options that are not set keep their value, so the configuration can be merged with options set in code,
+/// like hooks, with [`ClientOptionsConfig::apply`]. The host is either a region code, `"us"` or `"eu"`,
+/// or the base URL of a custom instance. Timeouts are numbers of seconds.
+///
+/// # Examples
+///
+/// ```
+/// use better_posthog::{ClientOptions, ClientOptionsConfig};
+///
+/// let config: ClientOptionsConfig = serde_json::from_str(
+///   r#"{ "api_key": "phc_your_api_key", "host": "eu", "request_timeout": 10 }"#,
+/// ).unwrap();
+///
+/// let options = config.apply(ClientOptions {
+///   before_send: vec![Box::new(|event| Some(event))],
+///   ..Default::default()
+/// });
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[non_exhaustive]
+pub struct ClientOptionsConfig {
+  /// The PostHog API key.
+  pub api_key: Option<ApiKey>,
+  /// The target PostHog host.
+  pub host: Option<Host>,
+  /// Timeout for graceful shutdown, in seconds.
+  #[serde(default, deserialize_with = "deserialize_seconds")]
+  pub shutdown_timeout: Option<Duration>,
+  /// Timeout of each request to PostHog, in seconds.
+  #[serde(default, deserialize_with = "deserialize_seconds")]
+  pub request_timeout: Option<Duration>,
+  /// Timeout for establishing a connection to PostHog, in seconds.
+  #[serde(default, deserialize_with = "deserialize_seconds")]
+  pub connect_timeout: Option<Duration>,
+  /// Deployment environment of the application, set as the `environment` property of events.
+  pub environment: Option<String>,
+  /// Release of the application, set as the `release` property of events.
+  pub release: Option<String>,
+  /// Distinct ID of the events captured without one, like a service account.
+  pub default_distinct_id: Option<String>,
+}
+
+impl ClientOptionsConfig {
+  /// Overrides the given options with the ones set in this configuration.
+  #[must_use]
+  pub fn apply(self, mut options: ClientOptions) -> ClientOptions {
+    if let Some(api_key) = self.api_key {
+      options.api_key = Some(api_key);
+    }
+    if let Some(host) = self.host {
+      options.host = host;
+    }
+    if let Some(shutdown_timeout) = self.shutdown_timeout {
+      options.shutdown_timeout = shutdown_timeout;
+    }
+    if let Some(request_timeout) = self.request_timeout {
+      options.request_timeout = request_timeout;
+    }
+    if self.connect_timeout.is_some() {
+      options.connect_timeout = self.connect_timeout;
+    }
+    if self.environment.is_some() {
+      options.environment = self.environment;
+    }
+    if self.release.is_some() {
+      options.release = self.release;
+    }
+    if self.default_distinct_id.is_some() {
+      options.default_distinct_id = self.default_distinct_id;
+    }
+    options
+  }
+}
+
+impl From<ClientOptionsConfig> for ClientOptions {
+  fn from(config: ClientOptionsConfig) -> Self {
+    config.apply(Self::default())
+  }
+}
+
+/// Effective client configuration, without secrets, for diagnostics.
+///
+/// Unlike [`ClientOptions`], it is safe to log: the API key is redacted and hooks are only counted.
+/// See [`events::effective_config`](crate::events::effective_config).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ConfigSnapshot {
+  /// The API key, redacted to its prefix, like `phc_***`.
+  pub api_key: String,
+  /// Base URL of the target PostHog host.
+  pub host: String,
+  /// Timeout for graceful shutdown.
+  pub shutdown_timeout: Duration,
+  /// Timeout of each request to PostHog.
+  pub request_timeout: Duration,
+  /// Timeout for establishing a connection to PostHog, if any.
+  pub connect_timeout: Option<Duration>,
+  /// Maximum number of tasks waiting in the queue, beyond which new events are dropped.
+  pub queue_capacity: usize,
+  /// Maximum number of requests to PostHog in flight at once.
+  pub max_concurrent_requests: usize,
+  /// Compression of request bodies.
+  pub compression: Compression,
+  /// Number of `before_send` hooks set at initialization.
+  pub before_send_hooks: usize,
+  /// Deployment environment of the application, if any.
+  pub environment: Option<String>,
+  /// Release of the application, if any.
+  pub release: Option<String>,
+}
+
+impl ConfigSnapshot {
+  /// Captures the effective configuration from the given options.
+  pub(crate) fn new(options: &ClientOptions) -> Self {
+    Self {
+      api_key: options.api_key.as_ref().map_or_else(String::new, ApiKey::redacted),
+      host: options.host.base_url().to_owned(),
+      shutdown_timeout: options.shutdown_timeout,
+      request_timeout: options.request_timeout,
+      connect_timeout: options.connect_timeout,
+      queue_capacity: QUEUE_CAPACITY,
+      max_concurrent_requests: options.max_concurrent_requests.max(1),
+      compression: options.compression,
+      before_send_hooks: options.before_send.len(),
+      environment: options.environment.clone(),
+      release: options.release.clone(),
+    }
+  }
+}
+
+/// Deserializes an optional duration from a number of seconds.
+fn deserialize_seconds<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+  Option::<f64>::deserialize(deserializer)?
+    .map(|seconds| Duration::try_from_secs_f64(seconds).map_err(de::Error::custom))
+    .transpose()
+}
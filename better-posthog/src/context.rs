@@ -1,29 +1,14 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::sync::LazyLock;
 
-/// Cached OS information to avoid repeated system calls.
-struct OsInfo {
-  name: String,
-  version: String,
-  arch: String,
-}
+use serde_json::Value;
 
-static OS_INFO: LazyLock<OsInfo> = LazyLock::new(|| {
-  let info = os_info::get();
-  OsInfo {
-    name: info.os_type().to_string(),
-    version: info.version().to_string(),
-    arch: std::env::consts::ARCH.to_string(),
-  }
-});
+use crate::client::ClientOptions;
 
-/// Library version parsed from Cargo.toml.
-static LIB_VERSION: LazyLock<semver::Version> = LazyLock::new(|| {
-  semver::Version::parse(env!("CARGO_PKG_VERSION")).expect("CARGO_PKG_VERSION should be valid semver")
-});
-
-/// Saturates the event with library and OS context metadata.
+/// Library and OS context properties, computed once to avoid repeated system calls and allocations.
 ///
-/// This adds the following properties to the event:
+/// This contains the following properties:
 /// - `$lib`: The crate name
 /// - `$lib_version`: Full version string
 /// - `$lib_version_major`: Major version number
@@ -32,36 +17,112 @@ static LIB_VERSION: LazyLock<semver::Version> = LazyLock::new(|| {
 /// - `$os`: Operating system name
 /// - `$os_version`: Operating system version
 /// - `$os_arch`: System architecture
-pub fn saturate_event(event: &mut crate::Event) {
+static CONTEXT: LazyLock<HashMap<String, Value>> = LazyLock::new(|| {
+  let version = semver::Version::parse(env!("CARGO_PKG_VERSION")).expect("CARGO_PKG_VERSION should be valid semver");
+  let os_info = os_info::get();
+
+  HashMap::from([
+    // Library metadata.
+    ("$lib".to_string(), Value::String(env!("CARGO_PKG_NAME").to_string())),
+    ("$lib_version".to_string(), Value::String(version.to_string())),
+    ("$lib_version_major".to_string(), Value::Number(version.major.into())),
+    ("$lib_version_minor".to_string(), Value::Number(version.minor.into())),
+    ("$lib_version_patch".to_string(), Value::Number(version.patch.into())),
+    // OS metadata.
+    ("$os".to_string(), Value::String(os_info.os_type().to_string())),
+    ("$os_version".to_string(), Value::String(os_info.version().to_string())),
+    (
+      "$os_arch".to_string(),
+      Value::String(std::env::consts::ARCH.to_string()),
+    ),
+  ])
+});
+
+/// Returns the context properties for the given options.
+///
+/// With a wrapper library, it is reported instead of this crate. This crate's name and version are then kept
+/// in the `$lib_core` and `$lib_core_version` properties, and the version components are only set if the library
+/// version is valid semver. The `environment` and `release` options are added as properties of the same name.
+pub fn context(options: &ClientOptions) -> Cow<'static, HashMap<String, Value>> {
+  if options.library.is_none() && options.environment.is_none() && options.release.is_none() {
+    return Cow::Borrowed(&*CONTEXT);
+  }
+
+  let mut context = CONTEXT.clone();
+  for (key, value) in [("environment", &options.environment), ("release", &options.release)] {
+    if let Some(value) = value {
+      context.insert(key.to_string(), Value::String(value.clone()));
+    }
+  }
+  let Some(library) = &options.library else {
+    return Cow::Owned(context);
+  };
+
+  context.insert(
+    "$lib_core".to_string(),
+    Value::String(env!("CARGO_PKG_NAME").to_string()),
+  );
+  context.insert(
+    "$lib_core_version".to_string(),
+    Value::String(env!("CARGO_PKG_VERSION").to_string()),
+  );
+  context.insert("$lib".to_string(), Value::String(library.name.clone()));
+  context.insert("$lib_version".to_string(), Value::String(library.version.clone()));
+  for key in VERSION_COMPONENTS {
+    context.remove(key);
+  }
+  for (key, value) in version_components(&library.version) {
+    context.insert(key.to_string(), value);
+  }
+  Cow::Owned(context)
+}
+
+/// Properties holding the numeric components of `$lib_version`.
+const VERSION_COMPONENTS: [&str; 3] = ["$lib_version_major", "$lib_version_minor", "$lib_version_patch"];
+
+/// Returns the `$lib_version_*` properties derived from the given version, or nothing if it isn't valid semver.
+fn version_components(version: &str) -> impl Iterator<Item = (&'static str, Value)> {
+  let version = semver::Version::parse(version).ok();
+  version.into_iter().flat_map(|version| {
+    VERSION_COMPONENTS
+      .into_iter()
+      .zip([version.major, version.minor, version.patch])
+      .map(|(key, component)| (key, Value::Number(component.into())))
+  })
+}
+
+/// Saturates the event with the library and OS context metadata returned by [`context`].
+///
+/// Properties already set on the event take precedence over the context ones.
+/// If the event sets its own `$lib_version`, the `$lib_version_*` components it doesn't set are derived from it
+/// rather than taken from the context, so they stay consistent with the reported version.
+/// Events without properties get a copy of the context map directly, skipping per-key lookups and rehashing.
+pub fn saturate_event(event: &mut crate::Event, context: &HashMap<String, Value>) {
   let props = &mut event.properties;
 
-  // Library metadata.
-  let version = &*LIB_VERSION;
-  props
-    .entry("$lib".to_string())
-    .or_insert_with(|| serde_json::Value::String(env!("CARGO_PKG_NAME").to_string()));
-  props
-    .entry("$lib_version".to_string())
-    .or_insert_with(|| serde_json::Value::String(version.to_string()));
-  props
-    .entry("$lib_version_major".to_string())
-    .or_insert_with(|| serde_json::Value::Number(version.major.into()));
-  props
-    .entry("$lib_version_minor".to_string())
-    .or_insert_with(|| serde_json::Value::Number(version.minor.into()));
-  props
-    .entry("$lib_version_patch".to_string())
-    .or_insert_with(|| serde_json::Value::Number(version.patch.into()));
+  if props.is_empty() {
+    HashMap::clone_from(props, context);
+    return;
+  }
+
+  let version_override = props
+    .get("$lib_version")
+    .filter(|version| context.get("$lib_version") != Some(*version))
+    .map(|version| version.as_str().unwrap_or_default().to_string());
 
-  // OS metadata.
-  let os_info = &*OS_INFO;
-  props
-    .entry("$os".to_string())
-    .or_insert_with(|| serde_json::Value::String(os_info.name.clone()));
-  props
-    .entry("$os_version".to_string())
-    .or_insert_with(|| serde_json::Value::String(os_info.version.clone()));
-  props
-    .entry("$os_arch".to_string())
-    .or_insert_with(|| serde_json::Value::String(os_info.arch.clone()));
+  props.reserve(context.len());
+  for (key, value) in context {
+    if version_override.is_some() && VERSION_COMPONENTS.contains(&key.as_str()) {
+      continue;
+    }
+    if !props.contains_key(key) {
+      props.insert(key.clone(), value.clone());
+    }
+  }
+
+  if let Some(version) = version_override {
+    for (key, value) in version_components(&version) {
+      props.entry(key.to_string()).or_insert(value);
+    }
+  }
 }
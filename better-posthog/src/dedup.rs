@@ -0,0 +1,40 @@
+//! Deduplication of events by `$insert_id` within a window of recently sent events.
+
+use std::collections::{HashSet, VecDeque};
+
+/// The `$insert_id`s of the most recent events, owned by the worker thread.
+pub struct DedupWindow {
+  capacity: usize,
+  /// Insert IDs in the order they were seen, the oldest first.
+  order: VecDeque<String>,
+  seen: HashSet<String>,
+}
+
+impl DedupWindow {
+  /// Creates an empty window remembering up to `capacity` insert IDs, at least one.
+  pub fn new(capacity: usize) -> Self {
+    let capacity = capacity.max(1);
+    Self {
+      capacity,
+      order: VecDeque::with_capacity(capacity),
+      seen: HashSet::with_capacity(capacity),
+    }
+  }
+
+  /// Records an insert ID, evicting the oldest one if the window is full.
+  ///
+  /// Returns `false` if the insert ID is already in the window.
+  pub fn insert(&mut self, insert_id: &str) -> bool {
+    if self.seen.contains(insert_id) {
+      return false;
+    }
+    if self.order.len() == self.capacity
+      && let Some(oldest) = self.order.pop_front()
+    {
+      self.seen.remove(&oldest);
+    }
+    self.order.push_back(insert_id.to_string());
+    self.seen.insert(insert_id.to_string());
+    true
+  }
+}
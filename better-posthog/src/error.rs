@@ -0,0 +1,125 @@
+//! Errors returned by the fallible APIs of the SDK.
+
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::events::BuildError;
+use crate::http::Delivery;
+use crate::timestamp::ParseTimestampError;
+
+/// Error returned by the fallible APIs of the SDK, like [`events::batch_blocking`](crate::events::batch_blocking).
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use better_posthog::{events, Error, Event};
+///
+/// match events::batch_blocking(vec![Event::new("order_imported", "user_123")], Duration::from_secs(10)) {
+///   Ok(_) => {}
+///   Err(Error::Timeout | Error::Transport(_)) => eprintln!("PostHog is unreachable, retrying later"),
+///   Err(e) => eprintln!("Import failed: {e}"),
+/// }
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+  /// The client is not initialized.
+  NotInitialized,
+  /// The events were not sent because the client is shutting down, or because they were removed from the queue
+  /// by [`events::drain`](crate::events::drain) or handed to the `on_shutdown_remaining` hook.
+  Drained,
+  /// The queue is full, so the events were dropped.
+  QueueFull,
+  /// The background worker thread panicked and was not respawned, so the events were dropped.
+  WorkerGone,
+  /// The events could not be serialized.
+  Serialization(serde_json::Error),
+  /// The request failed to reach PostHog, e.g. because of a network error.
+  Transport(String),
+  /// PostHog rejected the API key, or sending was disabled after it did.
+  Auth,
+  /// PostHog responded with a non-success status, and possibly a JSON body describing the error.
+  Status {
+    /// HTTP status of the response.
+    status: u16,
+    /// Body of the response, if it is valid JSON.
+    body: Option<Value>,
+  },
+  /// The events were not sent because the circuit breaker is open after repeated failures.
+  CircuitOpen,
+  /// The operation didn't complete within the timeout. Queued events may still be sent later.
+  Timeout,
+  /// The input is invalid, like an incomplete event or a malformed timestamp.
+  Validation(String),
+  /// The client options are invalid, see [`ClientOptions::validate`](crate::ClientOptions::validate).
+  Config(String),
+  /// The client was already initialized.
+  AlreadyInitialized,
+  /// Reading the input failed.
+  Io(std::io::Error),
+}
+
+impl Error {
+  /// Returns the delivery outcome corresponding to this error.
+  pub(crate) const fn delivery(&self) -> Delivery {
+    match self {
+      Self::Status { status, .. } => Delivery::from_status(*status),
+      Self::Auth => Delivery::Unauthorized,
+      Self::Transport(_) => Delivery::Unavailable,
+      Self::Serialization(_) | Self::Validation(_) | Self::Config(_) | Self::Io(_) => Delivery::Rejected,
+      Self::NotInitialized
+      | Self::Drained
+      | Self::AlreadyInitialized
+      | Self::QueueFull
+      | Self::WorkerGone
+      | Self::CircuitOpen
+      | Self::Timeout => Delivery::Skipped,
+    }
+  }
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::NotInitialized => f.write_str("PostHog client is not initialized"),
+      Self::Drained => f.write_str("PostHog events were drained before being sent"),
+      Self::QueueFull => f.write_str("PostHog queue is full"),
+      Self::WorkerGone => f.write_str("PostHog worker thread is gone"),
+      Self::Serialization(e) => write!(f, "failed to serialize events: {e}"),
+      Self::Transport(e) => write!(f, "failed to send request to PostHog: {e}"),
+      Self::Auth => f.write_str("PostHog rejected the API key"),
+      Self::Status { status, .. } => write!(f, "PostHog responded with status {status}"),
+      Self::CircuitOpen => f.write_str("PostHog circuit breaker is open"),
+      Self::Timeout => f.write_str("PostHog operation timed out"),
+      Self::Validation(e) => write!(f, "invalid input: {e}"),
+      Self::Config(e) => write!(f, "invalid PostHog client options: {e}"),
+      Self::AlreadyInitialized => f.write_str("PostHog client already initialized"),
+      Self::Io(e) => write!(f, "failed to read input: {e}"),
+    }
+  }
+}
+
+impl std::error::Error for Error {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Self::Serialization(e) => Some(e),
+      Self::Io(e) => Some(e),
+      _ => None,
+    }
+  }
+}
+
+impl From<BuildError> for Error {
+  fn from(error: BuildError) -> Self {
+    Self::Validation(error.to_string())
+  }
+}
+
+impl From<ParseTimestampError> for Error {
+  fn from(error: ParseTimestampError) -> Self {
+    Self::Validation(error.to_string())
+  }
+}
@@ -1,11 +1,36 @@
 //! Public API for capturing PostHog events.
 
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fmt;
+use std::io::BufRead;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
 
-use crate::client::CLIENT;
+use crate::client::{ApiKey, BeforeSendFn, CLIENT, Host, anonymous_id, capture_client};
+use crate::config::ConfigSnapshot;
+use crate::error::Error;
+use crate::http::BatchResponse;
+use crate::once::OnceStore;
+use crate::properties::Properties;
+use crate::timestamp::ParseTimestampError;
+use crate::worker::Destination;
+
+/// Property recording the probability with which an event was kept by sampling.
+const SAMPLE_RATE: &str = "$sample_rate";
+
+/// Number of events captured per batch by [`replay_from_reader`].
+const REPLAY_BATCH_SIZE: usize = 100;
+
+thread_local! {
+  /// Distinct ID of the events captured without one on this thread, set by [`with_distinct_id`].
+  static SCOPED_DISTINCT_ID: RefCell<Option<String>> = const { RefCell::new(None) };
+}
 
 /// Captures a single event and sends it to PostHog.
 ///
+/// Accepts an [`Event`] or anything convertible into one, like a `(name, distinct_id)` tuple.
+///
 /// If the client is not initialized or the queue is full, the event is dropped
 /// and a warning is logged. This function never blocks.
 ///
@@ -16,13 +41,287 @@ use crate::client::CLIENT;
 ///
 /// let event = Event::new("button_click", "user_123");
 /// events::capture(event);
+///
+/// events::capture(("page_view", "user_123"));
 /// ```
-pub fn capture(event: Event) {
-  if let Some(client) = CLIENT.get() {
-    client.worker.capture(event);
+pub fn capture(event: impl Into<Event>) {
+  try_capture(event);
+}
+
+/// Captures a single event like [`capture`], returning `true` if it was accepted into the queue.
+///
+/// It returns `false` if the event was dropped locally: because the queue is full, the client is not initialized
+/// or was shut down, or a `before_enqueue` hook discarded it. It says nothing about the delivery of the event,
+/// which happens later in the background. This lets producers react to backpressure right away.
+///
+/// # Examples
+///
+/// ```no_run
+/// use better_posthog::{events, Event};
+///
+/// if !events::try_capture(Event::new("file_uploaded", "user_123")) {
+///   eprintln!("PostHog queue is full, slowing down");
+/// }
+/// ```
+pub fn try_capture(event: impl Into<Event>) -> bool {
+  capture_client().is_some_and(|client| client.worker.capture(event.into(), None))
+}
+
+/// Captures an event only if the condition is `true`, e.g. for feature-gated events.
+///
+/// # Examples
+///
+/// ```no_run
+/// use better_posthog::{events, Event};
+///
+/// let analytics_enabled = true;
+/// events::capture_if(analytics_enabled, Event::new("page_view", "user_123"));
+/// ```
+pub fn capture_if(condition: bool, event: impl Into<Event>) {
+  if condition {
+    capture(event);
   }
 }
 
+/// Captures an event only once per installation, e.g. an `$app_installed` event on the first run.
+///
+/// The event is captured only if the key isn't in the [`OnceStore`] configured with the `once_store` option,
+/// and the key is inserted once the event is accepted into the queue, so it isn't captured again after a restart.
+/// Returns `true` if the event was accepted into the queue, like [`try_capture`].
+///
+/// The key is inserted before the event is delivered, so the event is lost if its delivery fails.
+/// If no `once_store` is configured, nothing is captured and a warning is logged.
+///
+/// # Examples
+///
+/// ```no_run
+/// use better_posthog::{events, ClientOptions, Event, FileOnceStore};
+///
+/// let _guard = better_posthog::init(ClientOptions {
+///   api_key: Some("phc_your_api_key".into()),
+///   once_store: Some(Box::new(FileOnceStore::new("/var/lib/my-app/posthog-once"))),
+///   ..Default::default()
+/// });
+///
+/// events::capture_once("installed", Event::new("$app_installed", "user_123"));
+/// ```
+pub fn capture_once(key: &str, event: impl Into<Event>) -> bool {
+  let Some(client) = capture_client() else {
+    return false;
+  };
+  let Some(store) = &client.once_store else {
+    log::warn!("PostHog `{key}` event not captured: no once_store is configured");
+    return false;
+  };
+  capture_once_with(store.as_ref(), key, event)
+}
+
+/// Captures an event only once per installation like [`capture_once`], with the given store instead of the
+/// configured one.
+///
+/// # Examples
+///
+/// ```no_run
+/// use better_posthog::{events, Event, FileOnceStore};
+///
+/// let store = FileOnceStore::new("/var/lib/my-app/onboarding");
+/// events::capture_once_with(&store, "tutorial_completed", Event::new("tutorial_completed", "user_123"));
+/// ```
+pub fn capture_once_with(store: &dyn OnceStore, key: &str, event: impl Into<Event>) -> bool {
+  static LOCK: Mutex<()> = Mutex::new(());
+
+  // Serialize the check and insert, so concurrent calls with the same key capture the event once.
+  let _lock = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+  if store.contains(key) {
+    log::trace!("PostHog `{key}` event was already captured once");
+    return false;
+  }
+  if !try_capture(event) {
+    return false;
+  }
+  if let Err(e) = store.insert(key) {
+    log::warn!("Failed to persist PostHog `{key}` once key, the event may be captured again: {e}");
+  }
+  true
+}
+
+/// Captures an event with the given probability, from `0.0` (never) to `1.0` (always).
+///
+/// Unlike sampling in a `before_send` hook, the decision is made on the calling thread,
+/// so sampled-out events never enter the queue.
+///
+/// Kept events get a `$sample_rate` property with the rate, so true counts can be extrapolated in PostHog by
+/// weighting each event with `1 / $sample_rate`. If the event was already sampled, e.g. by a caller sampling
+/// on its own, the existing `$sample_rate` is multiplied by the rate.
+///
+/// # Thread Safety
+///
+/// Sampling uses a thread-local, non-cryptographic RNG from [`fastrand`], seeded per thread,
+/// so it can be called from any thread without locking.
+///
+/// # Examples
+///
+/// ```no_run
+/// use better_posthog::{events, Event};
+///
+/// // Capture about 10% of scroll events.
+/// events::capture_sampled(Event::new("scroll", "user_123"), 0.1);
+/// ```
+pub fn capture_sampled(event: impl Into<Event>, rate: f64) {
+  if fastrand::f64() < rate {
+    let mut event = event.into();
+    record_sample_rate(&mut event, rate);
+    capture(event);
+  }
+}
+
+/// Sets the `$sample_rate` property of a sampled event, multiplying the rate of a previous sampling, if any.
+pub(crate) fn record_sample_rate(event: &mut Event, rate: f64) {
+  let rate = rate.min(1.0);
+  let sample_rate = event
+    .properties
+    .get(SAMPLE_RATE)
+    .and_then(serde_json::Value::as_f64)
+    .map_or(rate, |sample_rate| sample_rate * rate);
+  event.insert_property(SAMPLE_RATE, sample_rate);
+}
+
+/// Captures a single event and sends it to the given host with the given API key,
+/// instead of the ones configured at initialization.
+///
+/// The event still goes through the regular context saturation and `before_send` hooks.
+/// If the client is not initialized or the queue is full, the event is dropped
+/// and a warning is logged. This function never blocks.
+///
+/// # Examples
+///
+/// ```no_run
+/// use better_posthog::{events, ApiKey, Event, Host};
+///
+/// let event = Event::new("migration_started", "user_123");
+/// events::capture_to(event, &Host::EU, &ApiKey::from("phc_other_project_key"));
+/// ```
+pub fn capture_to(event: impl Into<Event>, host: &Host, api_key: &ApiKey) {
+  if let Some(client) = capture_client() {
+    let destination = Destination {
+      host: host.clone(),
+      api_key: api_key.clone(),
+    };
+    client.worker.capture(event.into(), Some(destination));
+  }
+}
+
+/// Captures a single event associated with the given groups and sends it to PostHog.
+///
+/// The groups are pairs of group type and group key, like a `HashMap<String, String>`.
+/// They are set as the `$groups` property, merged with any groups already set on the event.
+/// Use [`group_identify`] to set group properties.
+///
+/// If the client is not initialized or the queue is full, the event is dropped
+/// and a warning is logged. This function never blocks.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::collections::HashMap;
+///
+/// use better_posthog::{events, Event};
+///
+/// let event = Event::new("report_exported", "user_123");
+/// events::capture_with_groups(event, HashMap::from([("company".to_string(), "acme".to_string())]));
+/// ```
+pub fn capture_with_groups(event: impl Into<Event>, groups: impl IntoIterator<Item = (String, String)>) {
+  let mut event = event.into();
+  let mut merged = match event.properties.remove("$groups") {
+    Some(serde_json::Value::Object(existing)) => existing,
+    _ => serde_json::Map::new(),
+  };
+  merged.extend(
+    groups
+      .into_iter()
+      .map(|(group_type, group_key)| (group_type, serde_json::Value::String(group_key))),
+  );
+  event.insert_property("$groups", merged);
+  capture(event);
+}
+
+/// Sets properties of a group, creating the group if needed.
+///
+/// This captures a `$groupidentify` event with the properties in `$group_set`.
+///
+/// If the client is not initialized or the queue is full, the update is dropped
+/// and a warning is logged. This function never blocks.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::collections::HashMap;
+///
+/// use better_posthog::events;
+///
+/// events::group_identify("company", "acme", HashMap::from([("plan".to_string(), "enterprise".into())]));
+/// ```
+pub fn group_identify<T, K>(
+  group_type: T,
+  group_key: K,
+  properties: impl IntoIterator<Item = (String, serde_json::Value)>,
+) where
+  T: Into<String>,
+  K: Into<String>,
+{
+  let group_type = group_type.into();
+  let group_key = group_key.into();
+  let event = Event::builder()
+    .event("$groupidentify")
+    .distinct_id(format!("${group_type}_{group_key}"))
+    .property("$group_type", group_type)
+    .property("$group_key", group_key)
+    .property("$group_set", properties.into_iter().collect::<serde_json::Map<_, _>>())
+    .build();
+  capture(event);
+}
+
+/// Runs a closure in which events captured without a distinct ID default to the given one.
+///
+/// This is meant for backend services handling requests on behalf of different users, so the distinct ID doesn't
+/// have to be passed to every [`Event::new`] call in request handlers. Explicitly set distinct IDs, including
+/// [anonymous](Event::new_anonymous) ones, are kept. Scopes can be nested, the innermost one wins, and the previous
+/// distinct ID is restored when the closure returns or panics.
+///
+/// The distinct ID is stored in a thread-local, so it only applies to events captured on the calling thread,
+/// and doesn't follow async tasks moving between threads.
+///
+/// # Examples
+///
+/// ```no_run
+/// use better_posthog::{events, Event};
+///
+/// # fn handle_request(user_id: &str) {
+/// events::with_distinct_id(user_id, || {
+///   events::capture(Event::builder().event("order_created").build());
+///   events::capture(Event::new("admin_notified", "admin")); // Keeps its `admin` distinct ID.
+/// });
+/// # }
+/// ```
+pub fn with_distinct_id<T>(distinct_id: impl Into<String>, f: impl FnOnce() -> T) -> T {
+  /// Restores the previous distinct ID when the scope ends, even on panic.
+  struct Restore(Option<String>);
+
+  impl Drop for Restore {
+    fn drop(&mut self) {
+      SCOPED_DISTINCT_ID.set(self.0.take());
+    }
+  }
+
+  let _restore = Restore(SCOPED_DISTINCT_ID.replace(Some(distinct_id.into())));
+  f()
+}
+
+/// Returns the distinct ID set by the innermost [`with_distinct_id`] scope on this thread, if any.
+pub(crate) fn scoped_distinct_id() -> Option<String> {
+  SCOPED_DISTINCT_ID.with_borrow(Clone::clone)
+}
+
 /// Captures a batch of events and sends them to PostHog in a single request.
 ///
 /// If the client is not initialized or the queue is full, the batch is dropped
@@ -40,23 +339,384 @@ pub fn capture(event: Event) {
 /// events::batch(events);
 /// ```
 pub fn batch(events: Vec<Event>) {
-  if let Some(client) = CLIENT.get() {
+  if let Some(client) = capture_client() {
     client.worker.batch(events);
   }
 }
 
+/// Captures a batch of events and sends them to PostHog in a single request, blocking until PostHog responds.
+///
+/// Unlike [`batch`], the outcome is returned, e.g. for import tools implementing their own progress reporting
+/// and retries. The batch is queued behind pending events and processed like any other, except it is never
+/// split by the `max_batch_bytes` option, spooled, or retried.
+///
+/// # Errors
+///
+/// Returns an [`Error`] if PostHog didn't accept the batch, if the client is not initialized, or if no response
+/// was received within the timeout, e.g. because sending is [paused](pause).
+/// If the client shuts down before sending the batch, or the batch is taken from the queue by [`drain`] or handed
+/// to the `on_shutdown_remaining` hook, [`Error::Drained`] is returned.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use better_posthog::{events, Error, Event};
+///
+/// let events = vec![
+///   Event::new("order_imported", "user_123"),
+///   Event::new("order_imported", "user_456"),
+/// ];
+/// match events::batch_blocking(events, Duration::from_secs(10)) {
+///   Ok(response) => println!("Imported {} events", response.sent),
+///   Err(Error::Status { status, body }) => eprintln!("Rejected with status {status}: {body:?}"),
+///   Err(e) => eprintln!("Import failed: {e}"),
+/// }
+/// ```
+pub fn batch_blocking(events: Vec<Event>, timeout: Duration) -> Result<BatchResponse, Error> {
+  capture_client()
+    .ok_or(Error::NotInitialized)
+    .and_then(|client| client.worker.batch_blocking(events, timeout))
+}
+
+/// Captures events read from newline-delimited JSON, one event object per line, returning how many were read.
+///
+/// This is a testing and operations tool, e.g. to reproduce ingestion issues, load-test a self-hosted instance,
+/// or seed a development environment, rather than a hot-path API. Each non-empty line is parsed with
+/// [`Event::from_value`], and the events are captured with [`batch`] in batches of 100, so they go through
+/// the same pipeline as any other. As with [`batch`], batches are dropped if the queue is full.
+/// Events read before an invalid line are still captured.
+///
+/// # Errors
+///
+/// Returns [`Error::NotInitialized`] if the client is not initialized, [`Error::Io`] if reading fails,
+/// and [`Error::Validation`] with the line number if a line is not a valid event.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::BufReader;
+///
+/// use better_posthog::events;
+///
+/// let file = File::open("events.ndjson")?;
+/// let count = events::replay_from_reader(BufReader::new(file))?;
+/// println!("Replayed {count} events");
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn replay_from_reader(reader: impl BufRead) -> Result<usize, Error> {
+  let client = capture_client().ok_or(Error::NotInitialized)?;
+  let mut events = Vec::with_capacity(REPLAY_BATCH_SIZE);
+  let mut count = 0;
+  let mut result = Ok(());
+  for (index, line) in reader.lines().enumerate() {
+    let line = match line {
+      Ok(line) => line,
+      Err(e) => {
+        result = Err(Error::Io(e));
+        break;
+      }
+    };
+    if line.trim().is_empty() {
+      continue;
+    }
+    let event = serde_json::from_str(&line)
+      .map_err(|e| e.to_string())
+      .and_then(|value| {
+        Event::from_value(value).map_err(|e| match e {
+          Error::Validation(reason) => reason,
+          e => e.to_string(),
+        })
+      });
+    match event {
+      Ok(event) => events.push(event),
+      Err(reason) => {
+        result = Err(Error::Validation(format!("line {}: {reason}", index + 1)));
+        break;
+      }
+    }
+    if events.len() == REPLAY_BATCH_SIZE {
+      count += events.len();
+      client
+        .worker
+        .batch(std::mem::replace(&mut events, Vec::with_capacity(REPLAY_BATCH_SIZE)));
+    }
+  }
+  if !events.is_empty() {
+    count += events.len();
+    client.worker.batch(events);
+  }
+  result.map(|()| count)
+}
+
+/// Pauses sending events, e.g. while the application is in the background.
+///
+/// While paused, captured events are kept in the queue instead of being sent. Once the queue is full,
+/// new events are dropped and a warning is logged. [`flush`](crate::flush) returns `false` immediately,
+/// while [`shutdown`](crate::shutdown) and dropping the [`ClientGuard`](crate::ClientGuard) still send the queued events.
+///
+/// Does nothing if the client is not initialized or already paused.
+///
+/// # Examples
+///
+/// ```no_run
+/// use better_posthog::events;
+///
+/// events::pause();
+/// // ... the application is in the background ...
+/// events::resume();
+/// ```
+pub fn pause() {
+  if let Some(client) = CLIENT.get() {
+    client.worker.pause();
+  }
+}
+
+/// Resumes sending events after [`pause`], starting with the ones queued while paused.
+///
+/// Does nothing if the client is not initialized or not paused.
+pub fn resume() {
+  if let Some(client) = CLIENT.get() {
+    client.worker.resume();
+  }
+}
+
+/// Waits until all captured events are sent and no request is in flight, up to the specified timeout.
+///
+/// Unlike [`flush`](crate::flush), which only waits for the events captured before the call, this also waits
+/// for the ones captured meanwhile, which makes it a precise synchronization point for tests and job checkpoints.
+/// While sending is [paused](pause), queued events keep the worker busy until the timeout.
+///
+/// Returns `true` if the worker became idle within the timeout, or if the client is not initialized.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use better_posthog::{events, Event};
+///
+/// events::capture(Event::new("job_completed", "worker_1"));
+/// assert!(events::wait_idle(Duration::from_secs(5)));
+/// ```
+pub fn wait_idle(timeout: Duration) -> bool {
+  CLIENT.get().is_none_or(|client| client.worker.wait_idle(timeout))
+}
+
+/// Removes all events waiting in the queue without sending them, and returns them, e.g. to persist them elsewhere
+/// from a crash handler where network requests aren't safe.
+///
+/// The events are returned as they were captured, before context enrichment and `before_send` hooks, and without
+/// the destination set by [`capture_to`]. Raw events captured with [`capture_raw`] are returned if they can be
+/// parsed as events, and dropped otherwise. Batches waiting for a response in [`batch_blocking`] fail.
+/// Returns an empty vector if the client is not initialized.
+///
+/// # Thread Safety
+///
+/// It can be called from any thread, and never blocks on the network or the worker thread. It competes with the
+/// worker thread for the queued events: the ones the worker already took from the queue, including the ones being
+/// sent, are not returned, and events captured concurrently may be returned or sent. Pause sending with [`pause`]
+/// first, so the worker soon stops taking events. It allocates, so it is not async-signal-safe: call it from
+/// a panic hook or a thread handling signals, not from a signal handler itself.
+///
+/// # Examples
+///
+/// ```no_run
+/// use better_posthog::events;
+///
+/// std::panic::set_hook(Box::new(|_| {
+///   events::pause();
+///   for event in events::drain() {
+///     eprintln!("Unsent PostHog event: {}", event.name());
+///   }
+/// }));
+/// ```
+#[must_use]
+pub fn drain() -> Vec<Event> {
+  CLIENT.get().map_or_else(Vec::new, |client| client.worker.take_queued())
+}
+
+/// Appends a `before_send` hook after initialization, e.g. once the user consents to some data being collected.
+///
+/// The hook runs after the ones set at initialization, for events processed from now on, which may include
+/// events captured before the call but still queued. Does nothing if the client is not initialized.
+///
+/// # Examples
+///
+/// ```no_run
+/// use better_posthog::events;
+///
+/// events::add_before_send(Box::new(|mut event| {
+///   event.insert_property("consent", "granted");
+///   Some(event)
+/// }));
+/// ```
+pub fn add_before_send(hook: BeforeSendFn) {
+  if let Some(client) = CLIENT.get() {
+    client.worker.add_before_send(hook);
+  }
+}
+
+/// Returns the effective configuration of the client, with the API key redacted, e.g. to log it at startup.
+///
+/// Returns `None` if the client is not initialized.
+///
+/// # Examples
+///
+/// ```no_run
+/// if let Some(config) = better_posthog::events::effective_config() {
+///   println!("Sending PostHog events to {}", config.host);
+/// }
+/// ```
+#[must_use]
+pub fn effective_config() -> Option<ConfigSnapshot> {
+  CLIENT.get().map(|client| client.config.clone())
+}
+
+/// Captures a single event with binary attachments, uploaded by the `attachment_uploader` option.
+///
+/// Each attachment is replaced with a property holding the reference URL returned by the uploader,
+/// so large blobs like screenshots or log files don't end up in the event itself.
+/// Without an uploader, the event is sent without the attachments.
+///
+/// If the client is not initialized or the queue is full, the event is dropped
+/// and a warning is logged. This function never blocks, since uploads run in the background worker thread.
+///
+/// # Examples
+///
+/// ```no_run
+/// use better_posthog::{events, Attachment, Event};
+///
+/// # let screenshot = Vec::new();
+/// events::capture_with_attachments(
+///   Event::new("bug_reported", "user_123"),
+///   vec![Attachment::new("screenshot_url", screenshot).with_content_type("image/png")],
+/// );
+/// ```
+pub fn capture_with_attachments(event: impl Into<Event>, attachments: Vec<Attachment>) {
+  if let Some(client) = capture_client() {
+    client.worker.capture_with_attachments(event.into(), attachments);
+  }
+}
+
+/// Captures a pre-serialized event object and sends it to PostHog as is.
+///
+/// This is meant for advanced use cases, like forwarding events generated by other tools.
+/// The object must contain a non-empty `event` name and a `distinct_id`, either at the top level or within `properties`.
+/// Unlike [`capture`], the event is neither enriched with library/OS context nor passed to `before_send` hooks,
+/// but the `strip_null_properties` and `property_deny_list` options apply to its `properties` object.
+///
+/// If the event is invalid, the client is not initialized, or the queue is full, the event is dropped
+/// and a warning is logged. This function never blocks.
+///
+/// # Examples
+///
+/// ```no_run
+/// use better_posthog::events;
+///
+/// events::capture_raw(serde_json::json!({
+///   "event": "imported_event",
+///   "distinct_id": "user_123",
+///   "properties": { "source": "legacy" },
+///   "timestamp": "2024-01-01T00:00:00Z",
+/// }));
+/// ```
+pub fn capture_raw(event: serde_json::Value) {
+  if let Err(reason) = validate_raw(&event) {
+    log::warn!("PostHog raw event dropped: {reason}");
+    return;
+  }
+  if let Some(client) = capture_client() {
+    client.worker.capture_raw(event);
+  }
+}
+
+/// Checks that a raw event object has the fields required by PostHog.
+fn validate_raw(event: &serde_json::Value) -> Result<(), &'static str> {
+  let Some(object) = event.as_object() else {
+    return Err("event is not a JSON object");
+  };
+  if object
+    .get("event")
+    .and_then(serde_json::Value::as_str)
+    .is_none_or(str::is_empty)
+  {
+    return Err("missing `event` name");
+  }
+  let has_distinct_id = object.get("distinct_id").is_some_and(serde_json::Value::is_string)
+    || object
+      .get("properties")
+      .and_then(|properties| properties.get("distinct_id"))
+      .is_some_and(serde_json::Value::is_string);
+  if !has_distinct_id {
+    return Err("missing `distinct_id`");
+  }
+  Ok(())
+}
+
+/// Binary content attached to an event captured with [`capture_with_attachments`].
+///
+/// It is never sent to PostHog: the `attachment_uploader` option uploads it elsewhere,
+/// and the returned reference URL is set as the `property` of the event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Attachment {
+  /// Property of the event holding the reference URL of the uploaded attachment.
+  pub property: String,
+  /// MIME type of the content, if known.
+  pub content_type: Option<String>,
+  /// Content of the attachment.
+  pub data: Vec<u8>,
+}
+
+impl Attachment {
+  /// Creates an attachment whose reference URL is set as the given property.
+  pub fn new(property: impl Into<String>, data: impl Into<Vec<u8>>) -> Self {
+    Self {
+      property: property.into(),
+      content_type: None,
+      data: data.into(),
+    }
+  }
+
+  /// Sets the MIME type of the content.
+  #[must_use]
+  pub fn with_content_type(mut self, content_type: impl Into<String>) -> Self {
+    self.content_type = Some(content_type.into());
+    self
+  }
+}
+
 /// A PostHog analytics event.
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Event {
   /// The event name.
   pub event: String,
   /// The user's unique identifier.
-  pub distinct_id: String,
+  ///
+  /// If not set, it can still be assigned by a `before_send` hook.
+  /// Events that are still missing it at send time get the `default_distinct_id` option if set,
+  /// or an anonymous ID (see the `anonymous_id_version` and `anonymous_id_provider` options).
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub distinct_id: Option<String>,
   /// Custom properties attached to the event.
-  pub properties: HashMap<String, serde_json::Value>,
+  ///
+  /// An empty map doesn't allocate, so events without custom properties are cheap to create.
+  #[serde(default)]
+  pub properties: Properties,
   /// Optional ISO 8601 timestamp. If not set, PostHog uses server time.
   #[serde(skip_serializing_if = "Option::is_none")]
   pub timestamp: Option<String>,
+  /// Whether the distinct ID was generated by the SDK rather than supplied by the application,
+  /// see [`Event::is_anonymous`].
+  ///
+  /// It is managed by the SDK: set by [`Event::new_anonymous`] and cleared by [`Event::with_distinct_id`].
+  /// Set it to `false` when creating an event with a struct literal, unless its distinct ID is an anonymous one.
+  /// It is not serialized.
+  #[serde(skip)]
+  pub anonymous: bool,
 }
 
 impl Event {
@@ -72,13 +732,18 @@ impl Event {
   pub fn new<S: Into<String>>(event: S, distinct_id: S) -> Self {
     Self {
       event: event.into(),
-      distinct_id: distinct_id.into(),
-      properties: HashMap::new(),
+      distinct_id: Some(distinct_id.into()),
+      properties: Properties::new(),
       timestamp: None,
+      anonymous: false,
     }
   }
 
-  /// Creates a new event with a generated UUID v7 as the distinct ID.
+  /// Creates a new event with an anonymous distinct ID.
+  ///
+  /// The ID is returned by the `anonymous_id_provider` option if set, or is a UUID whose version is set by
+  /// the `anonymous_id_version` option, or UUID v7 if the client is not initialized.
+  /// The event is [anonymous](Event::is_anonymous), so it doesn't create a person profile by default.
   ///
   /// # Examples
   ///
@@ -90,9 +755,10 @@ impl Event {
   pub fn new_anonymous<S: Into<String>>(event: S) -> Self {
     Self {
       event: event.into(),
-      distinct_id: uuid::Uuid::now_v7().to_string(),
-      properties: HashMap::new(),
+      distinct_id: Some(anonymous_id()),
+      properties: Properties::new(),
       timestamp: None,
+      anonymous: true,
     }
   }
 
@@ -114,6 +780,183 @@ impl Event {
     EventBuilder::default()
   }
 
+  /// Returns the event name.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use better_posthog::Event;
+  ///
+  /// let event = Event::new("page_view", "user_123");
+  /// assert_eq!(event.name(), "page_view");
+  /// ```
+  #[must_use]
+  pub fn name(&self) -> &str {
+    &self.event
+  }
+
+  /// Returns the distinct ID, if set.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use better_posthog::Event;
+  ///
+  /// let event = Event::new("page_view", "user_123");
+  /// assert_eq!(event.distinct_id(), Some("user_123"));
+  /// ```
+  #[must_use]
+  pub fn distinct_id(&self) -> Option<&str> {
+    self.distinct_id.as_deref()
+  }
+
+  /// Returns the custom properties attached to the event.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use better_posthog::Event;
+  ///
+  /// let event = Event::builder().event("purchase").property("amount", 99.99).build();
+  /// assert_eq!(event.properties()["amount"], 99.99);
+  /// ```
+  #[must_use]
+  pub const fn properties(&self) -> &Properties {
+    &self.properties
+  }
+
+  /// Sets the distinct ID of the event, replacing any existing one.
+  ///
+  /// The event is no longer [anonymous](Event::is_anonymous) afterwards.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use better_posthog::Event;
+  ///
+  /// let event = Event::builder().event("login").build().with_distinct_id("user_123");
+  /// ```
+  #[must_use]
+  pub fn with_distinct_id<S: Into<String>>(mut self, distinct_id: S) -> Self {
+    self.distinct_id = Some(distinct_id.into());
+    self.anonymous = false;
+    self
+  }
+
+  /// Returns `true` if the distinct ID was generated by the SDK rather than supplied by the application.
+  ///
+  /// This is the case for events created with [`Event::new_anonymous`]. Events sent without a distinct ID
+  /// get an anonymous one too. Unless the `process_anonymous_persons` option is set, anonymous events are sent
+  /// with `$process_person_profile` set to `false`, so they don't create person profiles, which PostHog bills for.
+  /// Use [`Event::set_person_processing`] to override it for a single event.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use better_posthog::Event;
+  ///
+  /// assert!(Event::new_anonymous("page_view").is_anonymous());
+  /// assert!(!Event::new("page_view", "user_123").is_anonymous());
+  /// ```
+  #[must_use]
+  pub const fn is_anonymous(&self) -> bool {
+    self.anonymous
+  }
+
+  /// Sets whether PostHog should create or update a person profile for this event.
+  ///
+  /// This sets the `$process_person_profile` property. Disabling person processing for
+  /// high-volume events reduces costs, since such events are not tied to a person profile.
+  /// It is disabled by default for [anonymous](Event::is_anonymous) events.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use better_posthog::Event;
+  ///
+  /// let mut event = Event::new("heartbeat", "server-1");
+  /// event.set_person_processing(false);
+  /// ```
+  pub fn set_person_processing(&mut self, enabled: bool) {
+    self.insert_property("$process_person_profile", enabled);
+  }
+
+  /// Sets the name of the screen the event happened on, as the `$screen_name` property.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use better_posthog::Event;
+  ///
+  /// let mut event = Event::new("$screen", "user_123");
+  /// event.set_screen("Settings");
+  /// assert_eq!(event.properties()["$screen_name"], "Settings");
+  /// ```
+  pub fn set_screen<S: Into<String>>(&mut self, name: S) {
+    self.insert_property("$screen_name", name.into());
+  }
+
+  /// Sets the URL of the page the event happened on, as the `$current_url` property.
+  ///
+  /// If the URL is valid, its path is also set as the `$pathname` property.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use better_posthog::Event;
+  ///
+  /// let mut event = Event::new("$pageview", "user_123");
+  /// event.set_current_url("https://example.com/pricing?plan=pro");
+  /// assert_eq!(event.properties()["$pathname"], "/pricing");
+  /// ```
+  pub fn set_current_url<S: Into<String>>(&mut self, url: S) {
+    insert_current_url(&mut self.properties, url.into());
+  }
+
+  /// Sets the fingerprint PostHog error tracking groups an `$exception` event by, as the `$exception_fingerprint`
+  /// property.
+  ///
+  /// Exceptions with the same fingerprint are grouped into the same issue, e.g. by error type and failing operation,
+  /// instead of by PostHog's default grouping.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use better_posthog::Event;
+  ///
+  /// let mut event = Event::new("$exception", "user_123");
+  /// event.insert_property("$exception_message", "connection refused");
+  /// event.set_exception_fingerprint("db-connection-refused");
+  /// assert_eq!(event.properties()["$exception_fingerprint"], "db-connection-refused");
+  /// ```
+  pub fn set_exception_fingerprint<S: Into<String>>(&mut self, fingerprint: S) {
+    self.insert_property("$exception_fingerprint", fingerprint.into());
+  }
+
+  /// Sets a property of the person profile of the event's distinct ID, along with the event itself.
+  ///
+  /// This adds the property to the event's `$set` property, which PostHog applies to the person profile
+  /// when it ingests the event. It works with any event, so a regular capture can also update the person
+  /// without a separate `$identify` event. Use a dedicated `$identify` event instead to update a person
+  /// without capturing anything else, or to link an anonymous distinct ID to a known one.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use better_posthog::Event;
+  ///
+  /// let mut event = Event::new("plan_upgraded", "user_123");
+  /// event.set_person_property("plan", "enterprise");
+  /// assert_eq!(event.properties()["$set"]["plan"], "enterprise");
+  /// ```
+  pub fn set_person_property<K, V>(&mut self, key: K, value: V)
+  where
+    K: Into<String>,
+    V: Into<serde_json::Value>,
+  {
+    insert_person_property(&mut self.properties, key.into(), value.into());
+  }
+
   /// Inserts a property into the event.
   ///
   /// # Examples
@@ -132,6 +975,161 @@ impl Event {
   {
     self.properties.insert(key.into(), value.into());
   }
+
+  /// Inserts properties from any map or iterator of key-value pairs, like a `BTreeMap`, an `IndexMap`,
+  /// or a [`serde_json::Map`], without converting it to a `HashMap` first.
+  ///
+  /// Existing properties with the same keys are replaced.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use std::collections::BTreeMap;
+  ///
+  /// use better_posthog::Event;
+  ///
+  /// let properties = BTreeMap::from([("plan", "pro"), ("seats", "5")]);
+  ///
+  /// let mut event = Event::new("subscription_started", "user_789");
+  /// event.extend_properties(properties);
+  ///
+  /// // JSON objects work too.
+  /// if let serde_json::Value::Object(properties) = serde_json::json!({ "coupon": "WELCOME" }) {
+  ///   event.extend_properties(properties);
+  /// }
+  /// ```
+  pub fn extend_properties<I, K, V>(&mut self, properties: I)
+  where
+    I: IntoIterator<Item = (K, V)>,
+    K: Into<String>,
+    V: Into<serde_json::Value>,
+  {
+    extend_properties(&mut self.properties, properties);
+  }
+
+  /// Creates an event from a JSON object with `event`, `distinct_id`, `properties`, and `timestamp` fields.
+  ///
+  /// This is meant for event definitions received as JSON, e.g. from a frontend or a configuration file.
+  /// Unlike events captured with [`capture_raw`], the resulting event is enriched with library/OS context
+  /// and passed to `before_send` hooks like any other. Only `event` is required; the distinct ID may be set
+  /// within `properties` instead, as PostHog allows. Other fields are ignored.
+  ///
+  /// # Errors
+  ///
+  /// Returns [`Error::Validation`] if the value is not an object, `event` is missing or empty,
+  /// or a field has the wrong type, like a non-string `distinct_id` or a malformed `timestamp`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use better_posthog::Event;
+  ///
+  /// let event = Event::from_value(serde_json::json!({
+  ///   "event": "button_click",
+  ///   "distinct_id": "user_123",
+  ///   "properties": { "button_id": "submit" },
+  /// }))?;
+  /// assert_eq!(event.name(), "button_click");
+  /// assert_eq!(event.properties()["button_id"], "submit");
+  ///
+  /// assert!(Event::from_value(serde_json::json!({ "distinct_id": "user_123" })).is_err());
+  /// # Ok::<(), better_posthog::Error>(())
+  /// ```
+  pub fn from_value(value: serde_json::Value) -> Result<Self, Error> {
+    use serde_json::Value;
+
+    let invalid = |reason: &str| Error::Validation(reason.to_string());
+    let Value::Object(mut object) = value else {
+      return Err(invalid("event is not a JSON object"));
+    };
+
+    let event = match object.remove("event") {
+      Some(Value::String(event)) if !event.is_empty() => event,
+      _ => return Err(invalid("missing `event` name")),
+    };
+    let mut properties: Properties = match object.remove("properties") {
+      None | Some(Value::Null) => Properties::new(),
+      Some(Value::Object(properties)) => properties.into_iter().collect(),
+      Some(_) => return Err(invalid("`properties` is not a JSON object")),
+    };
+    let distinct_id = match object
+      .remove("distinct_id")
+      .or_else(|| properties.remove("distinct_id"))
+    {
+      None | Some(Value::Null) => None,
+      Some(Value::String(distinct_id)) => Some(distinct_id),
+      Some(_) => return Err(invalid("`distinct_id` is not a string")),
+    };
+    let timestamp = match object.remove("timestamp") {
+      None | Some(Value::Null) => None,
+      Some(Value::String(timestamp)) => {
+        crate::Timestamp::parse(&timestamp)?;
+        Some(timestamp)
+      }
+      Some(_) => return Err(invalid("`timestamp` is not a string")),
+    };
+
+    Ok(Self {
+      event,
+      distinct_id,
+      properties,
+      timestamp,
+      anonymous: false,
+    })
+  }
+}
+
+/// Inserts properties from an iterator of key-value pairs, reserving space for them upfront.
+fn extend_properties<I, K, V>(properties: &mut HashMap<String, serde_json::Value>, iter: I)
+where
+  I: IntoIterator<Item = (K, V)>,
+  K: Into<String>,
+  V: Into<serde_json::Value>,
+{
+  let iter = iter.into_iter();
+  properties.reserve(iter.size_hint().0);
+  properties.extend(iter.map(|(key, value)| (key.into(), value.into())));
+}
+
+impl<N, D> From<(N, D)> for Event
+where
+  N: Into<String>,
+  D: Into<String>,
+{
+  /// Creates an event from a `(name, distinct_id)` tuple.
+  fn from((event, distinct_id): (N, D)) -> Self {
+    Self {
+      event: event.into(),
+      distinct_id: Some(distinct_id.into()),
+      properties: Properties::new(),
+      timestamp: None,
+      anonymous: false,
+    }
+  }
+}
+
+impl<K, V> Extend<(K, V)> for Event
+where
+  K: Into<String>,
+  V: Into<serde_json::Value>,
+{
+  /// Inserts properties from an iterator of key-value pairs, like [`Event::extend_properties`].
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use std::collections::HashMap;
+  ///
+  /// use better_posthog::Event;
+  ///
+  /// let mut event = Event::new("report_exported", "user_123");
+  /// event.extend(HashMap::from([("format", "csv"), ("scope", "team")]));
+  /// event.extend([("rows", 120)]);
+  /// assert_eq!(event.properties.get_i64("rows"), Some(120));
+  /// ```
+  fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+    extend_properties(&mut self.properties, iter);
+  }
 }
 
 /// Builder for constructing [`Event`] instances.
@@ -139,7 +1137,7 @@ impl Event {
 pub struct EventBuilder {
   event: Option<String>,
   distinct_id: Option<String>,
-  properties: HashMap<String, serde_json::Value>,
+  properties: Properties,
   timestamp: Option<String>,
 }
 
@@ -152,6 +1150,8 @@ impl EventBuilder {
   }
 
   /// Sets the distinct ID.
+  ///
+  /// If not set, the distinct ID is resolved when the event is sent (see [`Event::distinct_id`]).
   #[must_use]
   pub fn distinct_id<S: Into<String>>(mut self, distinct_id: S) -> Self {
     self.distinct_id = Some(distinct_id.into());
@@ -165,7 +1165,66 @@ impl EventBuilder {
     self
   }
 
-  /// Sets the timestamp (ISO 8601 format).
+  /// Adds properties from any map or iterator of key-value pairs.
+  ///
+  /// See [`Event::extend_properties`].
+  #[must_use]
+  pub fn properties<I, K, V>(mut self, properties: I) -> Self
+  where
+    I: IntoIterator<Item = (K, V)>,
+    K: Into<String>,
+    V: Into<serde_json::Value>,
+  {
+    extend_properties(&mut self.properties, properties);
+    self
+  }
+
+  /// Sets whether PostHog should create or update a person profile for this event.
+  ///
+  /// See [`Event::set_person_processing`].
+  #[must_use]
+  pub fn person_processing(self, enabled: bool) -> Self {
+    self.property("$process_person_profile", enabled)
+  }
+
+  /// Sets the name of the screen the event happened on.
+  ///
+  /// See [`Event::set_screen`].
+  #[must_use]
+  pub fn screen<S: Into<String>>(self, name: S) -> Self {
+    self.property("$screen_name", name.into())
+  }
+
+  /// Sets the fingerprint PostHog error tracking groups the exception by.
+  ///
+  /// See [`Event::set_exception_fingerprint`].
+  #[must_use]
+  pub fn exception_fingerprint<S: Into<String>>(self, fingerprint: S) -> Self {
+    self.property("$exception_fingerprint", fingerprint.into())
+  }
+
+  /// Sets the URL of the page the event happened on.
+  ///
+  /// See [`Event::set_current_url`].
+  #[must_use]
+  pub fn current_url<S: Into<String>>(mut self, url: S) -> Self {
+    insert_current_url(&mut self.properties, url.into());
+    self
+  }
+
+  /// Sets a property of the person profile of the event's distinct ID, along with the event itself.
+  ///
+  /// See [`Event::set_person_property`].
+  #[must_use]
+  pub fn person_property<K: Into<String>, V: Into<serde_json::Value>>(mut self, key: K, value: V) -> Self {
+    insert_person_property(&mut self.properties, key.into(), value.into());
+    self
+  }
+
+  /// Sets the timestamp (RFC 3339 format).
+  ///
+  /// Prefer passing a [`Timestamp`](crate::Timestamp), which is guaranteed to be valid.
+  /// Otherwise, [`EventBuilder::try_build`] checks that it is valid.
   #[must_use]
   pub fn timestamp<S: Into<String>>(mut self, timestamp: S) -> Self {
     self.timestamp = Some(timestamp.into());
@@ -176,14 +1235,96 @@ impl EventBuilder {
   ///
   /// # Panics
   ///
-  /// Panics if `event` is not set.
+  /// Panics if `event` is not set. Use [`EventBuilder::try_build`] to handle this case gracefully.
+  /// An invalid timestamp doesn't panic, but is reported when the event is captured
+  /// (see the `strict_timestamps` option).
   #[must_use]
   pub fn build(self) -> Event {
-    Event {
-      event: self.event.expect("event name is required"),
-      distinct_id: self.distinct_id.unwrap_or_else(|| uuid::Uuid::now_v7().to_string()),
+    self.into_event().unwrap_or_else(|e| panic!("{e}"))
+  }
+
+  /// Builds the event, returning an error if it is incomplete or its timestamp is invalid.
+  ///
+  /// # Errors
+  ///
+  /// Returns [`BuildError::MissingEventName`] if `event` is not set,
+  /// and [`BuildError::InvalidTimestamp`] if the timestamp is not a valid RFC 3339 date-time.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use better_posthog::{BuildError, Event};
+  ///
+  /// let result = Event::builder().distinct_id("user_123").try_build();
+  /// assert!(matches!(result, Err(BuildError::MissingEventName)));
+  ///
+  /// let result = Event::builder().event("imported_event").timestamp("2024-13-01").try_build();
+  /// assert!(matches!(result, Err(BuildError::InvalidTimestamp(_))));
+  /// ```
+  pub fn try_build(self) -> Result<Event, BuildError> {
+    if let Some(timestamp) = &self.timestamp {
+      crate::Timestamp::parse(timestamp).map_err(BuildError::InvalidTimestamp)?;
+    }
+    self.into_event()
+  }
+
+  /// Builds the event without checking its timestamp.
+  fn into_event(self) -> Result<Event, BuildError> {
+    Ok(Event {
+      event: self.event.ok_or(BuildError::MissingEventName)?,
+      distinct_id: self.distinct_id,
       properties: self.properties,
       timestamp: self.timestamp,
+      anonymous: false,
+    })
+  }
+}
+
+/// Error returned by [`EventBuilder::try_build`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BuildError {
+  /// The event name was not set.
+  MissingEventName,
+  /// The timestamp is not a valid RFC 3339 date-time.
+  InvalidTimestamp(ParseTimestampError),
+}
+
+impl fmt::Display for BuildError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::MissingEventName => f.write_str("event name is required"),
+      Self::InvalidTimestamp(e) => e.fmt(f),
+    }
+  }
+}
+
+impl std::error::Error for BuildError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Self::MissingEventName => None,
+      Self::InvalidTimestamp(e) => Some(e),
     }
   }
 }
+
+/// Inserts the `$current_url` property, and the `$pathname` property if the URL is valid.
+fn insert_current_url(properties: &mut HashMap<String, serde_json::Value>, url: String) {
+  if let Ok(parsed) = reqwest::Url::parse(&url) {
+    properties.insert("$pathname".to_string(), parsed.path().into());
+  }
+  properties.insert("$current_url".to_string(), url.into());
+}
+
+/// Inserts a person property into the `$set` property, replacing it if it is not an object.
+fn insert_person_property(properties: &mut HashMap<String, serde_json::Value>, key: String, value: serde_json::Value) {
+  let set = properties
+    .entry("$set".to_string())
+    .or_insert_with(|| serde_json::Value::Object(serde_json::Map::new()));
+  if !set.is_object() {
+    *set = serde_json::Value::Object(serde_json::Map::new());
+  }
+  if let Some(set) = set.as_object_mut() {
+    set.insert(key, value);
+  }
+}
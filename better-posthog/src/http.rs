@@ -0,0 +1,581 @@
+//! HTTP delivery of events to PostHog.
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use bytes::Bytes;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::Event;
+use crate::client::{ApiKey, CaptureEndpoint, ClientOptions, Host, RequestBuilderFn, SerializeHookFn};
+use crate::error::Error;
+use crate::logging::{LogLevels, log_at};
+
+/// Payload for single event capture (`/i/v0/e/` or `/capture/`).
+#[derive(Serialize)]
+struct CapturePayload<'a> {
+  api_key: &'a str,
+  event: &'a str,
+  distinct_id: &'a str,
+  properties: &'a HashMap<String, Value>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  timestamp: Option<&'a str>,
+}
+
+/// Payload for batch event capture (`/batch/`).
+#[derive(Serialize)]
+struct BatchPayload<'a, T> {
+  api_key: &'a str,
+  batch: T,
+  /// Whether the events are imported historical data, see the `historical_migration` option.
+  #[serde(skip_serializing_if = "std::ops::Not::not")]
+  historical_migration: bool,
+}
+
+/// Single event within a batch.
+///
+/// The `distinct_id` is sent as a top-level field, which PostHog prefers over a `distinct_id` property,
+/// so a property with the same name is sent untouched instead of being overwritten.
+#[derive(Serialize)]
+struct BatchEvent<'a> {
+  event: &'a str,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  distinct_id: Option<&'a str>,
+  /// Properties of the event, serialized from it without cloning them.
+  properties: &'a HashMap<String, Value>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  timestamp: Option<&'a str>,
+}
+
+/// Status and body of a response from PostHog, read so that the connection can be reused.
+struct ServerResponse {
+  status: u16,
+  body: Vec<u8>,
+}
+
+impl ServerResponse {
+  /// Reads a response, whose body is left empty if it can't be read.
+  fn read(response: reqwest::blocking::Response) -> Self {
+    let status = response.status().as_u16();
+    let body = response.bytes().map_or_else(
+      |e| {
+        log::trace!("Failed to read PostHog response body: {e}");
+        Vec::new()
+      },
+      |body| body.to_vec(),
+    );
+    Self { status, body }
+  }
+
+  /// Returns `true` if PostHog rejected a compressed body because of its encoding rather than its payload,
+  /// responding with `415 Unsupported Media Type`, or `400 Bad Request` with an error about decompression.
+  fn rejects_encoding(&self) -> bool {
+    match self.status {
+      415 => true,
+      400 => {
+        let body = String::from_utf8_lossy(&self.body).to_ascii_lowercase();
+        body.contains("decompress") || body.contains("content-encoding")
+      }
+      _ => false,
+    }
+  }
+}
+
+/// Outcome of a delivery attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+  /// The events were accepted by PostHog.
+  Sent,
+  /// The events were rejected by PostHog or could not be serialized.
+  Rejected,
+  /// The events were rejected by PostHog because the API key is invalid.
+  Unauthorized,
+  /// PostHog could not be reached or responded with a server error.
+  Unavailable,
+  /// The events were dropped without attempting to send them, because the circuit breaker is open
+  /// or sending was disabled after an authentication error.
+  Skipped,
+}
+
+impl Delivery {
+  /// Returns the outcome of a request that got a response with the given status.
+  pub(crate) const fn from_status(status: u16) -> Self {
+    match status {
+      200..=299 => Self::Sent,
+      401 => Self::Unauthorized,
+      429 | 500..=599 => Self::Unavailable,
+      _ => Self::Rejected,
+    }
+  }
+}
+
+/// Response of PostHog to a batch sent with [`events::batch_blocking`](crate::events::batch_blocking).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BatchResponse {
+  /// HTTP status of the response, or `None` if no request was sent because `before_send` hooks discarded all events.
+  pub status: Option<u16>,
+  /// Number of events sent, after `before_send` hooks.
+  pub sent: usize,
+}
+
+/// Compression applied to request bodies sent to PostHog.
+///
+/// Compression trades CPU time for bandwidth, which is mostly worth it for large batches
+/// or on metered connections. If PostHog, e.g. a self-hosted instance, rejects the chosen encoding,
+/// requests are resent uncompressed and compression is disabled until the client is reinitialized.
+///
+/// # Examples
+///
+/// ```
+/// use better_posthog::Compression;
+///
+/// let options = better_posthog::ClientOptions {
+///   api_key: Some("phc_your_api_key".into()),
+///   compression: Compression::Gzip(6),
+///   ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Compression {
+  /// Send request bodies uncompressed.
+  #[default]
+  None,
+  /// Compress request bodies with gzip at the given level, from 0 (fastest) to 9 (smallest).
+  Gzip(u32),
+  /// Compress request bodies with zstd at the given level, from 1 (fastest) to 22 (smallest).
+  ///
+  /// Requires the `zstd` feature.
+  #[cfg(feature = "zstd")]
+  Zstd(i32),
+}
+
+impl Compression {
+  /// Returns the `Content-Encoding` header value, or `None` if bodies are sent uncompressed.
+  const fn content_encoding(self) -> Option<&'static str> {
+    match self {
+      Self::None => None,
+      Self::Gzip(_) => Some("gzip"),
+      #[cfg(feature = "zstd")]
+      Self::Zstd(_) => Some("zstd"),
+    }
+  }
+
+  /// Compresses a JSON body, returning `None` if bodies are sent uncompressed.
+  ///
+  /// Levels above the maximum supported level are clamped to it.
+  fn compress(self, json: &[u8]) -> io::Result<Option<Vec<u8>>> {
+    match self {
+      Self::None => Ok(None),
+      Self::Gzip(level) => {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::new(level.min(9)));
+        io::Write::write_all(&mut encoder, json)?;
+        encoder.finish().map(Some)
+      }
+      #[cfg(feature = "zstd")]
+      Self::Zstd(level) => zstd::stream::encode_all(json, level.min(*zstd::compression_level_range().end())).map(Some),
+    }
+  }
+}
+
+/// Events of a request passed to the `request_builder` hook.
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub enum GatewayPayload<'a> {
+  /// A single event, sent to the capture endpoint by default.
+  Event(&'a Event),
+  /// A batch of events, sent to `/batch/` by default.
+  Batch(&'a [Event]),
+  /// A pre-serialized event object captured with [`events::capture_raw`](crate::events::capture_raw).
+  Raw(&'a Value),
+}
+
+/// Request to PostHog serialized once before the first attempt, so retries send the exact same payload.
+pub struct PreparedRequest {
+  /// Describes the payload in log messages.
+  kind: &'static str,
+  body: PreparedBody,
+}
+
+/// Content of a prepared request.
+///
+/// Bodies are shared rather than copied by each attempt.
+enum PreparedBody {
+  /// Serialized payload sent to the given URL, with its compressed body unless bodies are sent uncompressed.
+  ///
+  /// The uncompressed body is kept in case PostHog rejects the encoding.
+  Json {
+    url: String,
+    json: Bytes,
+    compressed: Option<(Compression, Bytes)>,
+  },
+  /// Request built by the `request_builder` hook, cloned for each attempt.
+  Custom(Box<reqwest::blocking::Request>),
+}
+
+/// HTTP client used to send payloads to PostHog.
+pub struct HttpClient {
+  client: reqwest::blocking::Client,
+  compression: Compression,
+  /// Whether PostHog rejected compressed bodies, so they are sent uncompressed from now on.
+  compression_rejected: AtomicBool,
+  on_serialize: Option<SerializeHookFn>,
+  request_builder: Option<RequestBuilderFn>,
+  log_levels: LogLevels,
+  historical_migration: bool,
+  capture_endpoint: CaptureEndpoint,
+  /// Size of the last single event request body, used to pre-allocate the next one.
+  event_body_size: AtomicUsize,
+  /// Size of the last batch request body, used to pre-allocate the next one.
+  batch_body_size: AtomicUsize,
+}
+
+impl HttpClient {
+  /// Creates a new HTTP client from the given configuration.
+  ///
+  /// The `on_serialize` and `request_builder` hooks are moved out of the options into the client.
+  pub fn new(options: &mut ClientOptions) -> Self {
+    let mut builder = reqwest::blocking::Client::builder().timeout(options.request_timeout);
+    if let Some(connect_timeout) = options.connect_timeout {
+      builder = builder.connect_timeout(connect_timeout);
+    }
+    #[cfg(unix)]
+    if let Some(unix_socket) = options.unix_socket.clone() {
+      builder = builder.unix_socket(unix_socket);
+    }
+    let client = builder.build().unwrap_or_else(|e| {
+      log::error!("Failed to configure HTTP client, using default settings: {e}");
+      reqwest::blocking::Client::new()
+    });
+
+    Self {
+      client,
+      compression: options.compression,
+      compression_rejected: AtomicBool::new(false),
+      on_serialize: options.on_serialize.take(),
+      request_builder: options.request_builder.take(),
+      log_levels: options.log_levels,
+      historical_migration: options.historical_migration,
+      capture_endpoint: options.capture_endpoint,
+      event_body_size: AtomicUsize::new(0),
+      batch_body_size: AtomicUsize::new(0),
+    }
+  }
+
+  /// Prepares a request sending a single event via the configured capture endpoint, `/i/v0/e/` by default.
+  pub fn prepare_capture(&self, host: &Host, api_key: &ApiKey, event: &Event) -> Result<PreparedRequest, Error> {
+    if let Some(builder) = &self.request_builder {
+      return self.build_request(builder, host, api_key, GatewayPayload::Event(event), "event");
+    }
+    let payload = CapturePayload {
+      api_key: api_key.as_str(),
+      event: &event.event,
+      distinct_id: event.distinct_id.as_deref().unwrap_or_default(),
+      properties: &event.properties,
+      timestamp: event.timestamp.as_deref(),
+    };
+    self.prepare(
+      host.capture_url_for(self.capture_endpoint),
+      &payload,
+      &self.event_body_size,
+      "event",
+    )
+  }
+
+  /// Prepares a request sending a batch of events via `/batch/`.
+  pub fn prepare_batch(&self, host: &Host, api_key: &ApiKey, events: &[Event]) -> Result<PreparedRequest, Error> {
+    if let Some(builder) = &self.request_builder {
+      return self.build_request(builder, host, api_key, GatewayPayload::Batch(events), "batch");
+    }
+    self.prepare(
+      host.batch_url(),
+      &batch_payload(api_key, events, self.historical_migration),
+      &self.batch_body_size,
+      "batch",
+    )
+  }
+
+  /// Prepares a request sending a pre-serialized event object via `/batch/`.
+  pub fn prepare_raw(&self, host: &Host, api_key: &ApiKey, event: &Value) -> Result<PreparedRequest, Error> {
+    if let Some(builder) = &self.request_builder {
+      return self.build_request(builder, host, api_key, GatewayPayload::Raw(event), "raw event");
+    }
+    let payload = BatchPayload {
+      api_key: api_key.as_str(),
+      batch: [event],
+      historical_migration: self.historical_migration,
+    };
+    self.prepare(host.batch_url(), &payload, &self.event_body_size, "raw event")
+  }
+
+  /// Sends a batch of events to PostHog via `/batch/`, returning PostHog's response.
+  pub fn send_batch_with_response(
+    &self,
+    host: &Host,
+    api_key: &ApiKey,
+    events: &[Event],
+  ) -> Result<BatchResponse, Error> {
+    let response = self.post(&self.prepare_batch(host, api_key, events)?)?;
+    let status = response.status;
+    match Delivery::from_status(status) {
+      Delivery::Sent => {
+        log::trace!("PostHog batch request successful: status {status}");
+        Ok(BatchResponse {
+          status: Some(status),
+          sent: events.len(),
+        })
+      }
+      Delivery::Unauthorized => {
+        log_at!(
+          self.log_levels.auth_error,
+          "PostHog authentication failed: invalid API key"
+        );
+        Err(Error::Auth)
+      }
+      _ => {
+        log_at!(
+          self.log_levels.send_failure,
+          "PostHog batch request failed with status: {status}"
+        );
+        Err(Error::Status {
+          status,
+          body: serde_json::from_slice(&response.body).ok(),
+        })
+      }
+    }
+  }
+
+  /// Serializes the payload of a request to the given URL.
+  ///
+  /// The body is pre-allocated from the `body_size` of the previous request of the same shape, which is updated.
+  /// The `kind` describes the payload in log messages.
+  fn prepare(
+    &self,
+    url: String,
+    payload: &impl Serialize,
+    body_size: &AtomicUsize,
+    kind: &'static str,
+  ) -> Result<PreparedRequest, Error> {
+    let mut json = Vec::with_capacity(body_size.load(Ordering::Relaxed));
+    serde_json::to_writer(&mut json, payload).map_err(|e| {
+      log_at!(self.log_levels.serialization_error, "Failed to serialize {kind}: {e}");
+      Error::Serialization(e)
+    })?;
+    body_size.store(json.len(), Ordering::Relaxed);
+    log::trace!("Serialized {kind} payload size: {} bytes", json.len());
+    let compressed = self.compress(&json, kind)?;
+    Ok(PreparedRequest {
+      kind,
+      body: PreparedBody::Json {
+        url,
+        json: Bytes::from(json),
+        compressed,
+      },
+    })
+  }
+
+  /// Compresses a serialized payload with the configured compression, unless bodies are sent uncompressed.
+  ///
+  /// The `kind` describes the payload in log messages.
+  fn compress(&self, json: &[u8], kind: &'static str) -> Result<Option<(Compression, Bytes)>, Error> {
+    if self.compression_rejected.load(Ordering::Relaxed) {
+      return Ok(None);
+    }
+    match self.compression.compress(json) {
+      Ok(body) => Ok(body.map(|body| (self.compression, Bytes::from(body)))),
+      Err(e) => {
+        log_at!(self.log_levels.serialization_error, "Failed to compress {kind}: {e}");
+        Err(Error::Serialization(serde_json::Error::io(e)))
+      }
+    }
+  }
+
+  /// Builds a request with the `request_builder` hook.
+  ///
+  /// The `kind` describes the payload in log messages.
+  fn build_request(
+    &self,
+    builder: &RequestBuilderFn,
+    host: &Host,
+    api_key: &ApiKey,
+    payload: GatewayPayload<'_>,
+    kind: &'static str,
+  ) -> Result<PreparedRequest, Error> {
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| builder(host, api_key, payload))) {
+      Ok(Ok(request)) => Ok(PreparedRequest {
+        kind,
+        body: PreparedBody::Custom(Box::new(request)),
+      }),
+      Ok(Err(e)) => {
+        log_at!(
+          self.log_levels.serialization_error,
+          "Failed to build {kind} request with request_builder hook: {e}"
+        );
+        Err(Error::Validation(format!("request_builder hook failed: {e}")))
+      }
+      Err(_) => {
+        log::error!("Panic in request_builder hook, dropping {kind}");
+        Err(Error::Validation("request_builder hook panicked".into()))
+      }
+    }
+  }
+
+  /// Sends a prepared request to PostHog, returning the delivery outcome.
+  pub fn send(&self, request: &PreparedRequest) -> Delivery {
+    let kind = request.kind;
+    let response = match self.post(request) {
+      Ok(response) => response,
+      Err(e) => return e.delivery(),
+    };
+
+    let delivery = Delivery::from_status(response.status);
+    match delivery {
+      Delivery::Sent => log::trace!("PostHog {kind} request successful: status {}", response.status),
+      Delivery::Unauthorized => log_at!(
+        self.log_levels.auth_error,
+        "PostHog authentication failed: invalid API key"
+      ),
+      _ => log_at!(
+        self.log_levels.send_failure,
+        "PostHog {kind} request failed with status: {}",
+        response.status
+      ),
+    }
+    delivery
+  }
+
+  /// Posts a prepared request, returning the response whatever its status.
+  ///
+  /// If PostHog rejects a compressed body because of its encoding (see [`ServerResponse::rejects_encoding`]),
+  /// the payload is resent uncompressed, and compression is disabled for the lifetime of the client.
+  /// Other rejections are returned as is, so an invalid payload is never sent twice.
+  fn post(&self, request: &PreparedRequest) -> Result<ServerResponse, Error> {
+    let kind = request.kind;
+    let (url, json, compressed) = match &request.body {
+      PreparedBody::Json { url, json, compressed } => (url, json, compressed),
+      PreparedBody::Custom(request) => return self.post_custom(request, kind).map(ServerResponse::read),
+    };
+    let Some((encoding, body)) = compressed
+      .as_ref()
+      .filter(|_| !self.compression_rejected.load(Ordering::Relaxed))
+      .and_then(|(compression, body)| Some((compression.content_encoding()?, body)))
+    else {
+      return self.post_with(url, json, kind, None).map(ServerResponse::read);
+    };
+    let response = ServerResponse::read(self.post_with(url, body, kind, Some(encoding))?);
+    if !response.rejects_encoding() {
+      return Ok(response);
+    }
+
+    if !self.compression_rejected.swap(true, Ordering::Relaxed) {
+      log::warn!(
+        "PostHog rejected {encoding}-encoded {kind} with status {}, sending requests uncompressed from now on",
+        response.status
+      );
+    }
+    self.post_with(url, json, kind, None).map(ServerResponse::read)
+  }
+
+  /// Posts a serialized body to the given URL, with the given `Content-Encoding` if it is compressed.
+  ///
+  /// The body is shared with the prepared request, so it isn't copied.
+  /// The `kind` describes the payload in log messages.
+  fn post_with(
+    &self,
+    url: &str,
+    body: &Bytes,
+    kind: &'static str,
+    encoding: Option<&'static str>,
+  ) -> Result<reqwest::blocking::Response, Error> {
+    if let Some(hook) = &self.on_serialize
+      && std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| hook(body))).is_err()
+    {
+      log::error!("Panic in on_serialize hook");
+    }
+
+    let mut builder = self.client.post(url).header("Content-Type", "application/json");
+    if let Some(encoding) = encoding {
+      builder = builder.header("Content-Encoding", encoding);
+    }
+
+    builder.body(body.clone()).send().map_err(|e| {
+      log_at!(self.log_levels.send_failure, "Failed to send {kind} to PostHog: {e}");
+      Error::Transport(e.to_string())
+    })
+  }
+
+  /// Sends a copy of a request built by the `request_builder` hook.
+  ///
+  /// The `kind` describes the payload in log messages.
+  fn post_custom(
+    &self,
+    request: &reqwest::blocking::Request,
+    kind: &'static str,
+  ) -> Result<reqwest::blocking::Response, Error> {
+    let Some(request) = request.try_clone() else {
+      log_at!(
+        self.log_levels.send_failure,
+        "Failed to send {kind}: requests built by request_builder hook can't have a streamed body"
+      );
+      return Err(Error::Validation("request body can't be streamed".into()));
+    };
+
+    if let Some(hook) = &self.on_serialize
+      && let Some(body) = request.body().and_then(reqwest::blocking::Body::as_bytes)
+      && std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| hook(body))).is_err()
+    {
+      log::error!("Panic in on_serialize hook");
+    }
+
+    self.client.execute(request).map_err(|e| {
+      log_at!(self.log_levels.send_failure, "Failed to send {kind} to gateway: {e}");
+      Error::Transport(e.to_string())
+    })
+  }
+}
+
+/// Builds the payload of a batch request.
+fn batch_payload<'a>(
+  api_key: &'a ApiKey,
+  events: &'a [Event],
+  historical_migration: bool,
+) -> BatchPayload<'a, Vec<BatchEvent<'a>>> {
+  BatchPayload {
+    api_key: api_key.as_str(),
+    historical_migration,
+    batch: events
+      .iter()
+      .map(|event| BatchEvent {
+        event: &event.event,
+        distinct_id: event.distinct_id.as_deref(),
+        properties: &event.properties,
+        timestamp: event.timestamp.as_deref(),
+      })
+      .collect(),
+  }
+}
+
+/// Returns the estimated size of an event, or one of its property values, once serialized in a payload, in bytes.
+pub fn estimated_size(value: &impl Serialize) -> usize {
+  /// Writer counting the bytes written to it.
+  struct Counter(usize);
+
+  impl io::Write for Counter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+      self.0 += buf.len();
+      Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+      Ok(())
+    }
+  }
+
+  let mut counter = Counter(0);
+  // Serializing into the counter can't fail for events and JSON values, which only contain valid JSON data.
+  serde_json::to_writer(&mut counter, value).ok();
+  counter.0
+}
@@ -34,20 +34,63 @@
 //! // Guard is dropped here, triggering graceful shutdown.
 //! ```
 
+mod builder;
+mod circuit_breaker;
 mod client;
+mod config;
 mod context;
+mod dedup;
+mod error;
+mod http;
+mod logging;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod mirror;
+mod once;
+mod person;
+mod policy;
+mod properties;
+mod retry;
+mod spool;
+mod stats;
+mod timestamp;
+mod trace;
 mod worker;
 
-pub use client::{ApiKey, BeforeSendFn, ClientOptions, Host};
+pub use builder::ClientBuilder;
+pub use circuit_breaker::CircuitBreakerConfig;
+pub use client::{
+  AnonymousIdProviderFn, AnonymousIdVersion, ApiKey, AttachmentUploaderFn, AuthErrorHookFn, BeforeEnqueueFn,
+  BeforeSendFn, CaptureEndpoint, ClientOptions, Host, Library, LifecycleHookFn, OversizedEventPolicy, Region,
+  RequestBuilderFn, SerializeHookFn, ShutdownCapturePolicy, ShutdownRemainingFn, TraceContextFn,
+};
 use client::{CLIENT, Client};
+pub use config::{ClientOptionsConfig, ConfigSnapshot};
+pub use error::Error;
 
 pub mod events;
-pub use events::{Event, EventBuilder};
+pub mod logger;
+pub use events::{Attachment, BuildError, Event, EventBuilder};
+pub use http::{BatchResponse, Compression, GatewayPayload};
+pub use logging::LogLevels;
+pub use mirror::MirrorConfig;
+pub use once::{FileOnceStore, OnceStore};
+pub use policy::EventPolicy;
+pub use properties::Properties;
+pub use retry::{RetryConfig, RetryJitter, RetryPolicy};
+pub use stats::Stats;
+pub use timestamp::{ParseTimestampError, SkewedTimestampPolicy, Timestamp, TimestampGuardConfig, TimestampPrecision};
+pub use trace::TraceContext;
+pub use worker::WorkerLifecycle;
+
+#[cfg(feature = "test-util")]
+pub mod test_util;
 
 /// Guard that manages the PostHog client lifecycle.
 ///
 /// When dropped, this guard triggers graceful shutdown of the background worker,
-/// attempting to flush pending events within the configured timeout.
+/// attempting to send pending events within the configured timeout before the worker stops.
+/// If the client was already shut down with [`shutdown`], dropping the guard does nothing.
 ///
 /// # Examples
 ///
@@ -68,7 +111,7 @@ pub struct ClientGuard {
 impl Drop for ClientGuard {
   fn drop(&mut self) {
     if let Some(client) = CLIENT.get()
-      && !client.worker.flush(self.shutdown_timeout)
+      && !client.worker.drain(self.shutdown_timeout)
     {
       log::warn!(
         "PostHog shutdown timed out after {:?}, some events may be lost",
@@ -97,15 +140,23 @@ impl Drop for ClientGuard {
 ///   api_key: Some("phc_your_api_key".into()),
 ///   host: better_posthog::Host::EU,
 ///   shutdown_timeout: std::time::Duration::from_secs(5),
+///   ..Default::default()
 /// });
 /// ```
 pub fn init(options: impl Into<ClientOptions>) -> ClientGuard {
   let options = options.into();
+  if let Err(e) = options.validate() {
+    log::error!("{e}");
+  }
   let shutdown_timeout = options.shutdown_timeout;
+  client::INIT_CALLED.store(true, std::sync::atomic::Ordering::Relaxed);
 
-  if options.api_key.is_none() {
+  let Some(api_key) = &options.api_key else {
     log::warn!("PostHog client not initialized: no API key provided");
     return ClientGuard { shutdown_timeout };
+  };
+  if let Some(issue) = api_key.format_issue() {
+    log::warn!("PostHog API key may be misconfigured: {issue}");
   }
 
   assert!(
@@ -116,6 +167,96 @@ pub fn init(options: impl Into<ClientOptions>) -> ClientGuard {
   ClientGuard { shutdown_timeout }
 }
 
+/// Returns a builder that sets the client options fluently and initializes the client.
+///
+/// # Examples
+///
+/// ```no_run
+/// use better_posthog::Host;
+///
+/// let _guard = better_posthog::builder()
+///   .api_key("phc_your_api_key")
+///   .host(Host::EU)
+///   .shutdown_timeout(std::time::Duration::from_secs(5))
+///   .init();
+/// ```
+#[must_use]
+pub fn builder() -> ClientBuilder {
+  ClientBuilder::default()
+}
+
+/// Initializes the PostHog client like [`init`], but fails on invalid configuration instead of logging it.
+///
+/// # Errors
+///
+/// Returns [`Error::Config`] if no API key is provided or the options are invalid (see [`ClientOptions::validate`]),
+/// and [`Error::AlreadyInitialized`] if the client was already initialized.
+///
+/// # Examples
+///
+/// ```no_run
+/// let _guard = better_posthog::try_init("phc_your_api_key").expect("PostHog options should be valid");
+/// ```
+pub fn try_init(options: impl Into<ClientOptions>) -> Result<ClientGuard, Error> {
+  let options = options.into();
+  options.validate()?;
+  let shutdown_timeout = options.shutdown_timeout;
+  client::INIT_CALLED.store(true, std::sync::atomic::Ordering::Relaxed);
+
+  let Some(api_key) = &options.api_key else {
+    return Err(Error::Config("no API key provided".into()));
+  };
+  if let Some(issue) = api_key.format_issue() {
+    log::warn!("PostHog API key may be misconfigured: {issue}");
+  }
+  if CLIENT.get().is_some() || CLIENT.set(Client::new(options)).is_err() {
+    return Err(Error::AlreadyInitialized);
+  }
+
+  Ok(ClientGuard { shutdown_timeout })
+}
+
+/// Sends a single event synchronously, without initializing the global client or spawning a worker thread.
+///
+/// This is meant for scripts, health checks, and integration tests that only need to send one event,
+/// or to check that PostHog is reachable with the given options. The event is enriched and passed to the
+/// `before_send` hooks like captured events, then sent in a single request, without retries.
+/// The request and connection timeouts are capped at the given `timeout`, and mirrors are ignored.
+///
+/// Returns `Ok(())` without sending anything if a `before_send` hook discarded the event.
+///
+/// # Errors
+///
+/// Returns [`Error::Config`] if no API key is provided or the options are invalid,
+/// and the error of the request otherwise, like [`Error::Transport`] or [`Error::Status`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use better_posthog::Event;
+///
+/// let result = better_posthog::send_once("phc_your_api_key", Event::new("health_check", "server-1"), Duration::from_secs(5));
+/// if let Err(e) = result {
+///   eprintln!("PostHog is unreachable: {e}");
+/// }
+/// ```
+pub fn send_once(options: impl Into<ClientOptions>, event: Event, timeout: std::time::Duration) -> Result<(), Error> {
+  let mut options = options.into();
+  options.validate()?;
+  let Some(api_key) = &options.api_key else {
+    return Err(Error::Config("no API key provided".into()));
+  };
+  if let Some(issue) = api_key.format_issue() {
+    log::warn!("PostHog API key may be misconfigured: {issue}");
+  }
+
+  options.request_timeout = options.request_timeout.min(timeout);
+  options.connect_timeout = Some(options.connect_timeout.map_or(timeout, |connect| connect.min(timeout)));
+  worker::send_once(options, event)
+}
+
 /// Flushes pending events, waiting up to the specified timeout.
 ///
 /// Returns `true` if the flush completed within the timeout.
@@ -136,3 +277,105 @@ pub fn flush(timeout: std::time::Duration) -> bool {
     false
   }
 }
+
+/// Flushes pending events like [`flush`], returning how many events were still queued or being sent at the timeout.
+///
+/// Returns `0` if the flush completed within the timeout, or if the client is not initialized.
+/// The count includes events captured while waiting.
+///
+/// # Examples
+///
+/// ```no_run
+/// let remaining = better_posthog::flush_with_remaining(std::time::Duration::from_secs(5));
+/// if remaining > 0 {
+///   eprintln!("Flush timed out with {remaining} events pending");
+/// }
+/// ```
+#[must_use]
+pub fn flush_with_remaining(timeout: std::time::Duration) -> usize {
+  match CLIENT.get() {
+    Some(client) if !client.worker.flush(timeout) => client.worker.pending_events(),
+    Some(_) => 0,
+    None => {
+      log::warn!("PostHog client not initialized");
+      0
+    }
+  }
+}
+
+/// Sends pending events and stops the background worker, waiting up to the specified timeout.
+///
+/// This is an alternative to dropping the [`ClientGuard`] for applications that can't tie the client lifetime
+/// to a scope. Events captured meanwhile are handled by the `shutdown_capture_policy` option, and capturing events
+/// afterwards is a no-op. Calling it again, or when the client is not initialized, does nothing and returns `true`.
+///
+/// Returns `true` if all pending events were processed within the timeout.
+///
+/// # Examples
+///
+/// ```no_run
+/// if !better_posthog::shutdown(std::time::Duration::from_secs(5)) {
+///   eprintln!("Shutdown timed out, some events may be lost");
+/// }
+/// ```
+pub fn shutdown(timeout: std::time::Duration) -> bool {
+  CLIENT.get().is_none_or(|client| client.worker.drain(timeout))
+}
+
+/// Returns a snapshot of the client's runtime counters.
+///
+/// Returns `None` if the client is not initialized.
+///
+/// # Examples
+///
+/// ```no_run
+/// if let Some(stats) = better_posthog::stats() {
+///   println!("{} events sent, {} failed", stats.events_sent, stats.events_failed);
+/// }
+/// ```
+#[must_use]
+pub fn stats() -> Option<Stats> {
+  CLIENT.get().map(|client| client.worker.stats())
+}
+
+/// Returns a snapshot of the runtime counters of each mirror, in the order of [`ClientOptions::mirrors`].
+///
+/// Each mirror has its own queue and circuit breaker, so its counters and `circuit_open` flag reflect its own
+/// health, independently of the primary host reported by [`stats`]. Returns an empty vector if the client is not
+/// initialized.
+///
+/// # Examples
+///
+/// ```no_run
+/// for (index, stats) in better_posthog::mirror_stats().iter().enumerate() {
+///   if stats.circuit_open {
+///     eprintln!("PostHog mirror {index} is unavailable, {} events failed", stats.events_failed);
+///   }
+/// }
+/// ```
+#[must_use]
+pub fn mirror_stats() -> Vec<Stats> {
+  CLIENT
+    .get()
+    .map_or_else(Vec::new, |client| client.worker.mirror_stats())
+}
+
+/// Returns how full the event queue is, from `0.0` (empty) to `1.0` (full).
+///
+/// Once the queue is full, new events are dropped, so high-throughput producers can use it to throttle themselves
+/// before that happens. Returns `0.0` if the client is not initialized.
+///
+/// # Examples
+///
+/// ```no_run
+/// use better_posthog::{events, Event};
+///
+/// // Skip low-value events while the queue is filling up.
+/// if better_posthog::queue_pressure() < 0.8 {
+///   events::capture(Event::new("mouse_move", "user_123"));
+/// }
+/// ```
+#[must_use]
+pub fn queue_pressure() -> f64 {
+  CLIENT.get().map_or(0.0, |client| client.worker.queue_pressure())
+}
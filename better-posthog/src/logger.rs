@@ -0,0 +1,100 @@
+//! Integration with the [`log`] crate, capturing log records as PostHog events.
+//!
+//! [`PostHogLogger`] forwards records at or above a given level to PostHog as `$log` events,
+//! with `message`, `level`, and `target` properties. It can wrap another logger, so records
+//! are still written wherever they were before.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use better_posthog::logger::PostHogLogger;
+//! use log::LevelFilter;
+//!
+//! let _guard = better_posthog::init("phc_your_api_key");
+//!
+//! let logger = PostHogLogger::new(LevelFilter::Error).with_distinct_id("server-1");
+//! log::set_logger(Box::leak(Box::new(logger))).expect("logger should be set once");
+//! log::set_max_level(LevelFilter::Error);
+//!
+//! log::error!("Failed to process payment");
+//! ```
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+use crate::worker::THREAD_NAME;
+use crate::{Event, events};
+
+/// Logger capturing log records as PostHog events.
+///
+/// Records logged by this SDK, or from its background worker threads (e.g. by the HTTP client),
+/// are never captured, so capturing a record can't trigger the capture of another one.
+pub struct PostHogLogger {
+  level: LevelFilter,
+  distinct_id: Option<String>,
+  inner: Option<Box<dyn Log>>,
+}
+
+impl PostHogLogger {
+  /// Creates a logger capturing records at or above the given level.
+  #[must_use]
+  pub const fn new(level: LevelFilter) -> Self {
+    Self {
+      level,
+      distinct_id: None,
+      inner: None,
+    }
+  }
+
+  /// Sets the distinct ID of captured events.
+  ///
+  /// If not set, each event gets an anonymous distinct ID.
+  #[must_use]
+  pub fn with_distinct_id<S: Into<String>>(mut self, distinct_id: S) -> Self {
+    self.distinct_id = Some(distinct_id.into());
+    self
+  }
+
+  /// Sets a logger that receives all records, whether they are captured or not.
+  #[must_use]
+  pub fn with_inner<L: Log + 'static>(mut self, inner: L) -> Self {
+    self.inner = Some(Box::new(inner));
+    self
+  }
+
+  /// Returns `true` if the record should be captured as an event.
+  fn captures(&self, record: &Record<'_>) -> bool {
+    record.level() <= self.level
+      && !record.target().starts_with(env!("CARGO_CRATE_NAME"))
+      && std::thread::current().name() != Some(THREAD_NAME)
+  }
+}
+
+impl Log for PostHogLogger {
+  fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+    metadata.level() <= self.level || self.inner.as_ref().is_some_and(|inner| inner.enabled(metadata))
+  }
+
+  fn log(&self, record: &Record<'_>) {
+    if let Some(inner) = &self.inner {
+      inner.log(record);
+    }
+    if !self.captures(record) {
+      return;
+    }
+
+    let mut event = Event::builder()
+      .event("$log")
+      .property("message", record.args().to_string())
+      .property("level", record.level().as_str())
+      .property("target", record.target())
+      .build();
+    event.distinct_id.clone_from(&self.distinct_id);
+    events::capture(event);
+  }
+
+  fn flush(&self) {
+    if let Some(inner) = &self.inner {
+      inner.flush();
+    }
+  }
+}
@@ -0,0 +1,54 @@
+use log::LevelFilter;
+
+/// Log levels of the SDK's recurring operational messages, grouped by category.
+///
+/// Set a category to [`LevelFilter::Off`] to silence it.
+///
+/// # Examples
+///
+/// ```
+/// use log::LevelFilter;
+///
+/// let options = better_posthog::ClientOptions {
+///   api_key: Some("phc_your_api_key".into()),
+///   log_levels: better_posthog::LogLevels {
+///     send_failure: LevelFilter::Warn,
+///     queue_drop: LevelFilter::Off,
+///     ..Default::default()
+///   },
+///   ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogLevels {
+  /// Requests that failed to reach PostHog or were rejected by it (default: error).
+  pub send_failure: LevelFilter,
+  /// Events dropped because the queue is full, the worker is gone, or the client is shutting down (default: warn).
+  pub queue_drop: LevelFilter,
+  /// Payloads that could not be serialized (default: error).
+  pub serialization_error: LevelFilter,
+  /// Requests rejected by PostHog because of an invalid API key (default: error).
+  pub auth_error: LevelFilter,
+}
+
+impl Default for LogLevels {
+  fn default() -> Self {
+    Self {
+      send_failure: LevelFilter::Error,
+      queue_drop: LevelFilter::Warn,
+      serialization_error: LevelFilter::Error,
+      auth_error: LevelFilter::Error,
+    }
+  }
+}
+
+/// Logs a message at the level of the given [`LevelFilter`], unless it is [`LevelFilter::Off`].
+macro_rules! log_at {
+  ($filter:expr, $($arg:tt)+) => {
+    if let Some(level) = $filter.to_level() {
+      log::log!(level, $($arg)+);
+    }
+  };
+}
+
+pub(crate) use log_at;
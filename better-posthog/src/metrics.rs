@@ -0,0 +1,23 @@
+//! Export of the worker's counters through the [`metrics`](https://docs.rs/metrics) crate.
+
+/// Total number of events accepted into the queue.
+const EVENTS_CAPTURED: &str = "posthog_events_captured_total";
+/// Total number of events dropped because the queue was full, the worker was gone, or the client was shutting down.
+const EVENTS_DROPPED: &str = "posthog_events_dropped_total";
+/// Number of tasks currently waiting in the queue.
+const QUEUE_DEPTH: &str = "posthog_queue_depth";
+
+/// Reports events accepted into the queue to the installed `metrics` recorder, if any.
+pub fn captured(count: usize) {
+  ::metrics::counter!(EVENTS_CAPTURED).increment(count as u64);
+}
+
+/// Reports dropped events to the installed `metrics` recorder, if any.
+pub fn dropped(count: usize) {
+  ::metrics::counter!(EVENTS_DROPPED).increment(count as u64);
+}
+
+/// Reports the number of tasks waiting in the queue to the installed `metrics` recorder, if any.
+pub fn queue_depth(depth: usize) {
+  ::metrics::gauge!(QUEUE_DEPTH).set(f64::from(u32::try_from(depth).unwrap_or(u32::MAX)));
+}
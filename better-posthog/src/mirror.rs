@@ -0,0 +1,77 @@
+use crate::circuit_breaker::CircuitBreakerConfig;
+use crate::client::{ApiKey, ClientOptions, Host};
+use crate::retry::RetryConfig;
+
+/// Configuration of a secondary PostHog endpoint receiving a copy of every event, e.g. during a migration
+/// between a self-hosted and a cloud instance.
+///
+/// Each mirror has its own queue, worker thread, retries, and circuit breaker, so a slow or unavailable mirror
+/// never delays or fails the delivery to the primary host. Events are forwarded to mirrors once they are enriched
+/// and passed the `before_send` hooks, whatever the outcome of their delivery to the primary host.
+/// If a mirror's queue is full, the event is dropped for that mirror only.
+///
+/// Delivery settings like `compression`, `request_timeout`, or `max_batch_bytes` are the same as the primary host's.
+///
+/// # Examples
+///
+/// ```no_run
+/// use better_posthog::{ClientOptions, Host, MirrorConfig};
+///
+/// let _guard = better_posthog::init(ClientOptions {
+///   api_key: Some("phc_self_hosted_key".into()),
+///   host: Host::Custom("https://posthog.example.com".into()),
+///   mirrors: vec![MirrorConfig::new(Host::EU, "phc_cloud_key")],
+///   ..Default::default()
+/// });
+///
+/// if let Some(stats) = better_posthog::mirror_stats().first() {
+///   println!("{} events mirrored to the cloud, {} failed", stats.events_sent, stats.events_failed);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct MirrorConfig {
+  /// The host of the mirror.
+  pub host: Host,
+  /// The API key of the project receiving the events on the mirror.
+  pub api_key: ApiKey,
+  /// Retry strategy for the requests to the mirror (default: disabled).
+  pub retry: Option<RetryConfig>,
+  /// Circuit breaker pausing sends while the mirror is unavailable (default: enabled).
+  pub circuit_breaker: Option<CircuitBreakerConfig>,
+}
+
+impl MirrorConfig {
+  /// Creates a mirror sending events to the given host, with the default retry and circuit breaker settings.
+  pub fn new(host: Host, api_key: impl Into<ApiKey>) -> Self {
+    Self {
+      host,
+      api_key: api_key.into(),
+      retry: None,
+      circuit_breaker: Some(CircuitBreakerConfig::default()),
+    }
+  }
+
+  /// Returns the options of the mirror's worker, with the delivery settings of the primary options.
+  pub(crate) fn options(&self, primary: &ClientOptions) -> ClientOptions {
+    ClientOptions {
+      api_key: Some(self.api_key.clone()),
+      host: self.host.clone(),
+      capture_endpoint: primary.capture_endpoint,
+      circuit_breaker: self.circuit_breaker.clone(),
+      process_anonymous_persons: primary.process_anonymous_persons,
+      compression: primary.compression,
+      historical_migration: primary.historical_migration,
+      max_batch_bytes: primary.max_batch_bytes,
+      max_event_bytes: primary.max_event_bytes,
+      oversized_event_policy: primary.oversized_event_policy,
+      retry: self.retry.clone(),
+      log_levels: primary.log_levels,
+      request_timeout: primary.request_timeout,
+      connect_timeout: primary.connect_timeout,
+      max_concurrent_requests: primary.max_concurrent_requests,
+      idle_timeout: primary.idle_timeout,
+      respawn_on_panic: primary.respawn_on_panic,
+      ..Default::default()
+    }
+  }
+}
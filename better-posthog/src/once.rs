@@ -0,0 +1,100 @@
+//! Persistent record of the events captured once, like first-run events, with
+//! [`events::capture_once`](crate::events::capture_once).
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// Store of the keys of the events already captured with [`events::capture_once`](crate::events::capture_once).
+///
+/// Implement it to keep the keys wherever the application keeps its settings, or use [`FileOnceStore`].
+/// The store must persist the keys across restarts for events to be captured once per installation.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashSet;
+/// use std::sync::Mutex;
+///
+/// use better_posthog::OnceStore;
+///
+/// /// Store keeping the keys in memory, capturing events once per process.
+/// #[derive(Default)]
+/// struct MemoryStore(Mutex<HashSet<String>>);
+///
+/// impl OnceStore for MemoryStore {
+///   fn contains(&self, key: &str) -> bool {
+///     self.0.lock().unwrap().contains(key)
+///   }
+///
+///   fn insert(&self, key: &str) -> std::io::Result<()> {
+///     self.0.lock().unwrap().insert(key.to_string());
+///     Ok(())
+///   }
+/// }
+/// ```
+pub trait OnceStore: Send + Sync {
+  /// Returns `true` if the key was inserted before.
+  fn contains(&self, key: &str) -> bool;
+
+  /// Inserts the key, once its event was captured.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the key couldn't be persisted, in which case its event may be captured again.
+  fn insert(&self, key: &str) -> io::Result<()>;
+}
+
+/// Store persisting the keys in a file, one per line.
+///
+/// The file and its parent directories are created when the first key is inserted.
+///
+/// # Examples
+///
+/// ```no_run
+/// use better_posthog::{ClientOptions, FileOnceStore};
+///
+/// let options = ClientOptions {
+///   api_key: Some("phc_your_api_key".into()),
+///   once_store: Some(Box::new(FileOnceStore::new("/var/lib/my-app/posthog-once"))),
+///   ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone)]
+pub struct FileOnceStore {
+  path: PathBuf,
+}
+
+impl FileOnceStore {
+  /// Creates a store backed by the file at the given path.
+  pub fn new(path: impl Into<PathBuf>) -> Self {
+    Self { path: path.into() }
+  }
+}
+
+impl OnceStore for FileOnceStore {
+  fn contains(&self, key: &str) -> bool {
+    match fs::read_to_string(&self.path) {
+      Ok(content) => content.lines().any(|line| line == key),
+      Err(e) if e.kind() == io::ErrorKind::NotFound => false,
+      Err(e) => {
+        log::warn!("Failed to read once store {}: {e}", self.path.display());
+        false
+      }
+    }
+  }
+
+  fn insert(&self, key: &str) -> io::Result<()> {
+    if key.contains(['\n', '\r']) {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "key must not contain line breaks",
+      ));
+    }
+    if let Some(parent) = self.path.parent() {
+      fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+    writeln!(file, "{key}")
+  }
+}
@@ -0,0 +1,66 @@
+//! Local merging of person property updates sent together.
+
+use std::collections::{HashMap, HashSet};
+
+use serde_json::Value;
+
+use crate::Event;
+
+/// Removes the person property updates of events that are overridden by other events of the same person.
+///
+/// PostHog applies `$set` and `$unset` updates with last-write-wins, and `$set_once` updates with first-write-wins,
+/// in the order it processes events. Within a batch, or across concurrent requests, that order may differ from the
+/// capture order. Keeping only the last `$set` or `$unset` of each property and the first `$set_once` of each
+/// property, in capture order, makes the resulting person state independent of the processing order.
+///
+/// Updates left empty are removed. Events without a distinct ID are left untouched.
+pub fn merge_person_properties(events: &mut [&mut Event]) {
+  let mut written: HashMap<String, HashSet<String>> = HashMap::new();
+  for event in events.iter_mut().rev() {
+    if !(event.properties.contains_key("$set") || event.properties.contains_key("$unset")) {
+      continue;
+    }
+    let Some(distinct_id) = event.distinct_id.clone() else {
+      continue;
+    };
+    let written = written.entry(distinct_id).or_default();
+
+    let mut keys = Vec::new();
+    if let Some(Value::Object(set)) = event.properties.get_mut("$set") {
+      set.retain(|key, _| !written.contains(key));
+      keys.extend(set.keys().cloned());
+    }
+    if let Some(Value::Array(unset)) = event.properties.get_mut("$unset") {
+      unset.retain(|key| key.as_str().is_none_or(|key| !written.contains(key)));
+      keys.extend(unset.iter().filter_map(Value::as_str).map(str::to_owned));
+    }
+    written.extend(keys);
+    remove_if_empty(event, "$set");
+    remove_if_empty(event, "$unset");
+  }
+
+  let mut written_once: HashMap<String, HashSet<String>> = HashMap::new();
+  for event in events.iter_mut() {
+    let Some(Value::Object(set_once)) = event.properties.get_mut("$set_once") else {
+      continue;
+    };
+    let Some(distinct_id) = &event.distinct_id else {
+      continue;
+    };
+    let written = written_once.entry(distinct_id.clone()).or_default();
+    set_once.retain(|key, _| written.insert(key.clone()));
+    remove_if_empty(event, "$set_once");
+  }
+}
+
+/// Removes a person property update that has no properties left.
+fn remove_if_empty(event: &mut Event, key: &str) {
+  let is_empty = match event.properties.get(key) {
+    Some(Value::Object(properties)) => properties.is_empty(),
+    Some(Value::Array(properties)) => properties.is_empty(),
+    _ => false,
+  };
+  if is_empty {
+    event.properties.remove(key);
+  }
+}
@@ -0,0 +1,71 @@
+//! Declarative capture policies matched against event names.
+
+use crate::Event;
+use crate::events::record_sample_rate;
+
+/// What to do with the events whose name matches a pattern of the `event_policies` option.
+///
+/// # Examples
+///
+/// ```
+/// use better_posthog::EventPolicy;
+///
+/// let options = better_posthog::ClientOptions {
+///   api_key: Some("phc_your_api_key".into()),
+///   event_policies: vec![
+///     ("debug_health".into(), EventPolicy::Keep),
+///     ("debug_*".into(), EventPolicy::Drop),
+///     ("page_view".into(), EventPolicy::Sample(0.1)),
+///   ],
+///   ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[non_exhaustive]
+pub enum EventPolicy {
+  /// Capture the event, e.g. to exempt some events from a broader pattern listed after it.
+  #[default]
+  Keep,
+  /// Drop the event.
+  Drop,
+  /// Capture the event with the given probability, between 0 and 1, setting its `$sample_rate` property
+  /// like [`events::capture_sampled`](crate::events::capture_sampled).
+  Sample(f64),
+}
+
+/// Applies the policy of the first pattern matching the event name, returning `None` if the event is discarded.
+pub fn apply(policies: &[(String, EventPolicy)], mut event: Event) -> Option<Event> {
+  let Some((pattern, policy)) = policies.iter().find(|(pattern, _)| matches(pattern, &event.event)) else {
+    return Some(event);
+  };
+  match *policy {
+    EventPolicy::Keep => Some(event),
+    EventPolicy::Sample(rate) if fastrand::f64() < rate => {
+      record_sample_rate(&mut event, rate);
+      Some(event)
+    }
+    EventPolicy::Drop | EventPolicy::Sample(_) => {
+      log::trace!("{} event was dropped by the policy of `{pattern}`", event.event);
+      None
+    }
+  }
+}
+
+/// Returns `true` if the name matches the pattern, in which `*` matches any sequence of characters.
+fn matches(pattern: &str, name: &str) -> bool {
+  let mut parts = pattern.split('*');
+  let Some(mut rest) = parts.next().and_then(|prefix| name.strip_prefix(prefix)) else {
+    return false;
+  };
+  let parts: Vec<&str> = parts.collect();
+  let Some((suffix, middle)) = parts.split_last() else {
+    return rest.is_empty();
+  };
+  for part in middle {
+    let Some(index) = rest.find(part) else {
+      return false;
+    };
+    rest = &rest[index + part.len()..];
+  }
+  rest.ends_with(suffix)
+}
@@ -0,0 +1,155 @@
+//! Properties of events, with typed accessors.
+
+use std::collections::{HashMap, hash_map};
+use std::ops::{Deref, DerefMut};
+
+use serde_json::Value;
+
+/// Properties of an [`Event`](crate::Event), mapping keys to JSON values.
+///
+/// It dereferences to the underlying `HashMap`, so the whole map API is available, and adds typed accessors
+/// that spare matching on [`serde_json::Value`], e.g. in `before_send` hooks. It is serialized as a plain map.
+///
+/// # Examples
+///
+/// ```
+/// use better_posthog::Event;
+///
+/// let mut event = Event::builder()
+///   .event("purchase")
+///   .distinct_id("user_123")
+///   .property("plan", "pro")
+///   .property("amount", 99.99)
+///   .build();
+///
+/// assert_eq!(event.properties.get_str("plan"), Some("pro"));
+/// assert_eq!(event.properties.get_f64("amount"), Some(99.99));
+///
+/// event.properties.set("currency", "EUR");
+/// for (key, value) in &event.properties {
+///   println!("{key}: {value}");
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct Properties(HashMap<String, Value>);
+
+impl Properties {
+  /// Creates empty properties, without allocating.
+  #[must_use]
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Returns the value of a property if it is a string.
+  #[must_use]
+  pub fn get_str(&self, key: &str) -> Option<&str> {
+    self.0.get(key).and_then(Value::as_str)
+  }
+
+  /// Returns the value of a property if it is a number, converted to `f64`.
+  #[must_use]
+  pub fn get_f64(&self, key: &str) -> Option<f64> {
+    self.0.get(key).and_then(Value::as_f64)
+  }
+
+  /// Returns the value of a property if it is an integer that fits in an `i64`.
+  #[must_use]
+  pub fn get_i64(&self, key: &str) -> Option<i64> {
+    self.0.get(key).and_then(Value::as_i64)
+  }
+
+  /// Returns the value of a property if it is a boolean.
+  #[must_use]
+  pub fn get_bool(&self, key: &str) -> Option<bool> {
+    self.0.get(key).and_then(Value::as_bool)
+  }
+
+  /// Sets a property, returning its previous value, if any.
+  pub fn set<K: Into<String>, V: Into<Value>>(&mut self, key: K, value: V) -> Option<Value> {
+    self.0.insert(key.into(), value.into())
+  }
+
+  /// Returns the underlying map.
+  #[must_use]
+  pub fn into_inner(self) -> HashMap<String, Value> {
+    self.0
+  }
+}
+
+impl Deref for Properties {
+  type Target = HashMap<String, Value>;
+
+  fn deref(&self) -> &Self::Target {
+    &self.0
+  }
+}
+
+impl DerefMut for Properties {
+  fn deref_mut(&mut self) -> &mut Self::Target {
+    &mut self.0
+  }
+}
+
+impl From<HashMap<String, Value>> for Properties {
+  fn from(properties: HashMap<String, Value>) -> Self {
+    Self(properties)
+  }
+}
+
+impl<K: Into<String>, V: Into<Value>> FromIterator<(K, V)> for Properties {
+  fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+    Self(
+      iter
+        .into_iter()
+        .map(|(key, value)| (key.into(), value.into()))
+        .collect(),
+    )
+  }
+}
+
+impl<K: Into<String>, V: Into<Value>> Extend<(K, V)> for Properties {
+  /// Inserts properties from an iterator of key-value pairs, converting their keys and values.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use better_posthog::Properties;
+  ///
+  /// let mut properties: Properties = [("plan", "pro")].into_iter().collect();
+  /// properties.extend([("seats", 5)]);
+  /// assert_eq!(properties.get_i64("seats"), Some(5));
+  /// ```
+  fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+    let iter = iter.into_iter();
+    self.0.reserve(iter.size_hint().0);
+    self.0.extend(iter.map(|(key, value)| (key.into(), value.into())));
+  }
+}
+
+impl IntoIterator for Properties {
+  type Item = (String, Value);
+  type IntoIter = hash_map::IntoIter<String, Value>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.0.into_iter()
+  }
+}
+
+impl<'a> IntoIterator for &'a Properties {
+  type Item = (&'a String, &'a Value);
+  type IntoIter = hash_map::Iter<'a, String, Value>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.0.iter()
+  }
+}
+
+impl<'a> IntoIterator for &'a mut Properties {
+  type Item = (&'a String, &'a mut Value);
+  type IntoIter = hash_map::IterMut<'a, String, Value>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.0.iter_mut()
+  }
+}
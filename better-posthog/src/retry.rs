@@ -0,0 +1,111 @@
+use std::time::Duration;
+
+/// Configuration of retries of requests that failed because PostHog is unavailable.
+///
+/// Requests failing with a network error, a server error, or a `429 Too Many Requests` response are retried
+/// with exponential backoff. Retrying a request that actually reached PostHog would count its events twice,
+/// unless they carry an `$insert_id` property that PostHog uses to deduplicate them. Hence, only requests
+/// whose events all have an `$insert_id` are retried, see [`RetryPolicy`]. The request body is serialized once,
+/// so every attempt sends the exact same events, with the same `$insert_id` and timestamp.
+///
+/// Retries are performed by the background worker, delaying the events queued behind them.
+/// Backoff delays are randomized according to the [`RetryJitter`] strategy, so clients failing at the same time,
+/// e.g. during a brief PostHog outage, don't retry in lockstep.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let options = better_posthog::ClientOptions {
+///   api_key: Some("phc_your_api_key".into()),
+///   retry: Some(better_posthog::RetryConfig {
+///     max_retries: 5,
+///     initial_backoff: Duration::from_secs(1),
+///     ..Default::default()
+///   }),
+///   ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone)]
+pub struct RetryConfig {
+  /// Maximum number of retries of a failed request (default: 3).
+  pub max_retries: u32,
+  /// Delay before the first retry, doubled after each attempt (default: 500 milliseconds).
+  pub initial_backoff: Duration,
+  /// Maximum delay between retries (default: 10 seconds).
+  pub max_backoff: Duration,
+  /// Which events are safe to retry (default: [`RetryPolicy::GenerateInsertId`]).
+  pub policy: RetryPolicy,
+  /// Randomization of the delay between retries (default: [`RetryJitter::Equal`]).
+  pub jitter: RetryJitter,
+}
+
+impl Default for RetryConfig {
+  fn default() -> Self {
+    Self {
+      max_retries: 3,
+      initial_backoff: Duration::from_millis(500),
+      max_backoff: Duration::from_secs(10),
+      policy: RetryPolicy::default(),
+      jitter: RetryJitter::default(),
+    }
+  }
+}
+
+impl RetryConfig {
+  /// Returns the delay before the given retry, starting from 1, randomized according to the jitter strategy.
+  pub(crate) fn backoff(&self, retry: u32) -> Duration {
+    let backoff = self
+      .initial_backoff
+      .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+      .min(self.max_backoff);
+    match self.jitter {
+      RetryJitter::None => backoff,
+      RetryJitter::Full => backoff.mul_f64(fastrand::f64()),
+      RetryJitter::Equal => backoff / 2 + (backoff / 2).mul_f64(fastrand::f64()),
+    }
+  }
+}
+
+/// Strategy randomizing the delay between retries, to spread the retries of many clients over time.
+///
+/// # Examples
+///
+/// ```
+/// use better_posthog::{RetryConfig, RetryJitter};
+///
+/// let options = better_posthog::ClientOptions {
+///   api_key: Some("phc_your_api_key".into()),
+///   retry: Some(RetryConfig {
+///     jitter: RetryJitter::Full,
+///     ..Default::default()
+///   }),
+///   ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RetryJitter {
+  /// Wait exactly the exponential backoff delay.
+  None,
+  /// Wait a random delay between zero and the backoff delay, spreading retries the most.
+  Full,
+  /// Wait half the backoff delay plus a random delay up to the other half, keeping a minimum delay.
+  #[default]
+  Equal,
+}
+
+/// Policy deciding which events are safe to retry, based on their `$insert_id` property.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RetryPolicy {
+  /// Generate an `$insert_id` for events that don't have one before they are first sent, so all events can be retried.
+  ///
+  /// Raw events captured with [`events::capture_raw`](crate::events::capture_raw) are sent as is,
+  /// so they are only retried if they already have an `$insert_id` within their `properties`.
+  #[default]
+  GenerateInsertId,
+  /// Only retry requests whose events all have an `$insert_id` set by the application.
+  RequireInsertId,
+}
@@ -0,0 +1,164 @@
+//! On-disk spool of queued events, used to resend them after a crash.
+//!
+//! The spool is an append-only NDJSON file: each line holds one serialized [`Event`].
+//! The events of a task are appended as a record when it is enqueued, and the file is rewritten without the record
+//! once the task is handled, so the file only holds events at startup if the previous run crashed before handling them.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+use crate::Event;
+
+/// Identifier of the record holding the events of a queued task.
+pub type RecordId = u64;
+
+/// Handle to the spool file, shared by the threads enqueuing events and the worker thread.
+pub struct Spool {
+  path: PathBuf,
+  file: Option<File>,
+  /// Serialized events of the tasks not handled yet, by record.
+  records: BTreeMap<RecordId, Vec<u8>>,
+  next_record: RecordId,
+  /// Whether the file may contain events, so it needs to be cleared.
+  dirty: bool,
+}
+
+impl Spool {
+  /// Creates a spool backed by the file at the given path. The file is opened lazily.
+  pub const fn new(path: PathBuf) -> Self {
+    Self {
+      path,
+      file: None,
+      records: BTreeMap::new(),
+      next_record: 0,
+      dirty: false,
+    }
+  }
+
+  /// Serializes events into a record, returning `None` if one of them can't be serialized.
+  pub fn encode(events: &[Event]) -> Option<Vec<u8>> {
+    let mut buffer = Vec::new();
+    for event in events {
+      if let Err(e) = serde_json::to_writer(&mut buffer, event) {
+        log::error!("Failed to serialize spooled event: {e}");
+        return None;
+      }
+      buffer.push(b'\n');
+    }
+    Some(buffer)
+  }
+
+  /// Reads the events left over by a previous run, keeping them as a record until it is removed.
+  ///
+  /// Lines that can't be parsed, like a record truncated by a crash, are skipped.
+  pub fn recover(&mut self) -> Option<(RecordId, Vec<Event>)> {
+    let file = match File::open(&self.path) {
+      Ok(file) => {
+        self.dirty = true;
+        file
+      }
+      Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+      Err(e) => {
+        log::warn!("Failed to open PostHog spool {}: {e}", self.path.display());
+        return None;
+      }
+    };
+
+    let mut events = Vec::new();
+    let mut buffer = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+      let line = match line {
+        Ok(line) => line,
+        Err(e) => {
+          log::warn!("Failed to read PostHog spool {}: {e}", self.path.display());
+          break;
+        }
+      };
+      if line.trim().is_empty() {
+        continue;
+      }
+      match serde_json::from_str(&line) {
+        Ok(event) => {
+          events.push(event);
+          buffer.extend_from_slice(line.as_bytes());
+          buffer.push(b'\n');
+        }
+        Err(e) => log::warn!("Skipping corrupt record {} of PostHog spool: {e}", index + 1),
+      }
+    }
+    if events.is_empty() {
+      self.persist();
+      return None;
+    }
+    let record = self.reserve();
+    self.records.insert(record, buffer);
+    Some((record, events))
+  }
+
+  /// Reserves the identifier of a record to append.
+  pub const fn reserve(&mut self) -> RecordId {
+    self.next_record += 1;
+    self.next_record
+  }
+
+  /// Appends a record of events encoded with [`Spool::encode`].
+  pub fn append(&mut self, record: RecordId, buffer: Vec<u8>) {
+    self.dirty = true;
+    // Records are written with a single call, so a crash can only truncate the last one.
+    if let Err(e) = self.file().and_then(|file| file.write_all(&buffer)) {
+      log::warn!("Failed to write PostHog spool {}: {e}", self.path.display());
+    }
+    self.records.insert(record, buffer);
+  }
+
+  /// Removes the given records from the spool.
+  pub fn remove(&mut self, records: &[RecordId]) {
+    let mut removed = false;
+    for record in records {
+      removed |= self.records.remove(record).is_some();
+    }
+    if removed {
+      self.persist();
+    }
+  }
+
+  /// Writes the remaining records to the file, replacing it so that a crash leaves either version intact.
+  fn persist(&mut self) {
+    if self.records.is_empty() {
+      if std::mem::take(&mut self.dirty)
+        && let Err(e) = self.file().and_then(|file| file.set_len(0))
+      {
+        log::warn!("Failed to clear PostHog spool {}: {e}", self.path.display());
+      }
+      return;
+    }
+
+    let mut temporary = OsString::from(&self.path);
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+    // The file is reopened on the next append, as it can't be replaced while open on some platforms.
+    self.file = None;
+    let result = File::create(&temporary)
+      .and_then(|mut file| {
+        for buffer in self.records.values() {
+          file.write_all(buffer)?;
+        }
+        Ok(())
+      })
+      .and_then(|()| fs::rename(&temporary, &self.path));
+    if let Err(e) = result {
+      log::warn!("Failed to rewrite PostHog spool {}: {e}", self.path.display());
+    }
+  }
+
+  /// Returns the spool file, opening it in append mode if needed.
+  fn file(&mut self) -> io::Result<&mut File> {
+    if self.file.is_none() {
+      self.file = Some(OpenOptions::new().create(true).append(true).open(&self.path)?);
+    }
+    Ok(self.file.as_mut().expect("spool file was just opened"))
+  }
+}
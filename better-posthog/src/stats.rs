@@ -0,0 +1,126 @@
+//! Runtime counters of the background worker.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+#[cfg(feature = "metrics")]
+use crate::metrics;
+
+/// Snapshot of the client's runtime counters.
+///
+/// All counters are cumulative since [`init`](crate::init), except for `queue_depth`.
+/// With the `metrics` feature, the number of captured and dropped events and the queue depth are also reported as they
+/// change to the installed [`metrics`](https://docs.rs/metrics) recorder, as `posthog_events_captured_total`,
+/// `posthog_events_dropped_total`, and `posthog_queue_depth`.
+///
+/// # Examples
+///
+/// ```no_run
+/// if let Some(stats) = better_posthog::stats() {
+///   println!("{} events dropped, {} queued", stats.events_dropped, stats.queue_depth);
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Stats {
+  /// Number of events accepted into the queue.
+  pub events_captured: u64,
+  /// Number of events dropped because the queue was full, the worker was gone, or the client was shutting down.
+  pub events_dropped: u64,
+  /// Number of events discarded by `before_enqueue` or `before_send` hooks, or by the `timestamp_guard`.
+  pub events_filtered: u64,
+  /// Number of single events exceeding `max_event_bytes`, whatever the `oversized_event_policy`.
+  pub events_oversized: u64,
+  /// Number of events dropped because their `$insert_id` was recently sent, with the `dedup_window` option.
+  pub events_deduplicated: u64,
+  /// Number of events successfully delivered to PostHog.
+  pub events_sent: u64,
+  /// Number of events that failed to be delivered, including the ones dropped while the circuit breaker is open.
+  pub events_failed: u64,
+  /// Number of tasks currently waiting in the queue.
+  pub queue_depth: usize,
+  /// Whether sending is paused by the circuit breaker because PostHog appears to be unavailable.
+  pub circuit_open: bool,
+  /// Whether the worker thread panicked and was not respawned, so new events are dropped.
+  pub worker_gone: bool,
+}
+
+/// Shared atomic counters updated by the worker and its handle.
+#[derive(Debug, Default)]
+pub struct Counters {
+  pub captured: AtomicU64,
+  pub dropped: AtomicU64,
+  pub filtered: AtomicU64,
+  pub oversized: AtomicU64,
+  pub deduplicated: AtomicU64,
+  pub sent: AtomicU64,
+  pub failed: AtomicU64,
+  pub circuit_open: AtomicBool,
+  pub worker_gone: AtomicBool,
+  /// Whether the captured and dropped events and the queue depth are reported through the `metrics` crate.
+  #[cfg(feature = "metrics")]
+  pub export_metrics: bool,
+}
+
+impl Counters {
+  /// Creates zeroed counters, reported through the `metrics` crate if `export_metrics` is set.
+  #[cfg(feature = "metrics")]
+  pub fn new(export_metrics: bool) -> Self {
+    Self {
+      export_metrics,
+      ..Default::default()
+    }
+  }
+
+  /// Creates zeroed counters.
+  #[cfg(not(feature = "metrics"))]
+  pub fn new(_export_metrics: bool) -> Self {
+    Self::default()
+  }
+
+  /// Increments the given counter by `count`.
+  pub fn add(counter: &AtomicU64, count: usize) {
+    counter.fetch_add(count as u64, Ordering::Relaxed);
+  }
+
+  /// Counts events accepted into the queue.
+  pub fn add_captured(&self, count: usize) {
+    Self::add(&self.captured, count);
+    #[cfg(feature = "metrics")]
+    if self.export_metrics {
+      metrics::captured(count);
+    }
+  }
+
+  /// Counts events dropped before reaching the queue.
+  pub fn add_dropped(&self, count: usize) {
+    Self::add(&self.dropped, count);
+    #[cfg(feature = "metrics")]
+    if self.export_metrics {
+      metrics::dropped(count);
+    }
+  }
+
+  /// Reports the number of tasks waiting in the queue through the `metrics` crate, if enabled.
+  #[cfg(feature = "metrics")]
+  pub fn export_queue_depth(&self, depth: usize) {
+    if self.export_metrics {
+      metrics::queue_depth(depth);
+    }
+  }
+
+  /// Takes a snapshot of the counters.
+  pub fn snapshot(&self, queue_depth: usize) -> Stats {
+    Stats {
+      events_captured: self.captured.load(Ordering::Relaxed),
+      events_dropped: self.dropped.load(Ordering::Relaxed),
+      events_filtered: self.filtered.load(Ordering::Relaxed),
+      events_oversized: self.oversized.load(Ordering::Relaxed),
+      events_deduplicated: self.deduplicated.load(Ordering::Relaxed),
+      events_sent: self.sent.load(Ordering::Relaxed),
+      events_failed: self.failed.load(Ordering::Relaxed),
+      queue_depth,
+      circuit_open: self.circuit_open.load(Ordering::Relaxed),
+      worker_gone: self.worker_gone.load(Ordering::Relaxed),
+    }
+  }
+}
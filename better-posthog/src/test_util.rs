@@ -0,0 +1,130 @@
+//! Utilities for testing code that captures PostHog events, without network access.
+//!
+//! Available with the `test-util` feature.
+//!
+//! The global client can only be initialized once per process, so the first call to [`init`] or [`init_with`]
+//! installs an in-memory transport and every later call returns a handle to the same recorded events.
+//! Since tests in the same binary run in parallel, prefer asserting on event names or properties that are unique
+//! to the test rather than on the total number of recorded events.
+//!
+//! # Examples
+//!
+//! ```
+//! use better_posthog::{events, test_util, Event};
+//!
+//! let captured = test_util::init();
+//!
+//! events::capture(Event::builder().event("signup").distinct_id("user_123").property("plan", "pro").build());
+//!
+//! captured.assert_captured("signup");
+//! assert_eq!(captured.find("signup").unwrap().properties["plan"], "pro");
+//! captured.assert_not_captured("checkout");
+//! ```
+
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::time::Duration;
+
+use crate::client::{CLIENT, Client, ClientOptions};
+use crate::worker::Transport;
+use crate::{Event, flush};
+
+/// Timeout used to wait for the worker before inspecting recorded events.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Events recorded by the in-memory transport.
+static CAPTURED: OnceLock<CapturedEvents> = OnceLock::new();
+
+/// Initializes the global client with an in-memory transport and default options.
+///
+/// See [`init_with`] for details.
+#[must_use]
+pub fn init() -> CapturedEvents {
+  init_with(ClientOptions::default())
+}
+
+/// Initializes the global client with an in-memory transport and the given options.
+///
+/// Events go through the regular context saturation and `before_send` hooks,
+/// but are recorded in memory instead of being sent to PostHog.
+/// A placeholder API key is used if none is provided.
+///
+/// Only the first call initializes the client; later calls ignore `options` and return the same recorder.
+///
+/// # Panics
+///
+/// Panics if the global client was already initialized with [`crate::init`].
+#[must_use]
+pub fn init_with(options: impl Into<ClientOptions>) -> CapturedEvents {
+  CAPTURED
+    .get_or_init(|| {
+      let mut options = options.into();
+      options.api_key.get_or_insert_with(|| "phc_test".into());
+
+      let captured = CapturedEvents { events: Arc::default() };
+      let transport = Transport::Memory(captured.events.clone());
+      assert!(
+        CLIENT.set(Client::with_transport(options, transport)).is_ok(),
+        "PostHog client already initialized"
+      );
+      captured
+    })
+    .clone()
+}
+
+/// Handle to the events recorded by the in-memory transport.
+#[derive(Debug, Clone)]
+pub struct CapturedEvents {
+  events: Arc<Mutex<Vec<Event>>>,
+}
+
+impl CapturedEvents {
+  /// Waits for the worker to process pending events and returns a copy of all recorded events.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the worker does not process pending events in time.
+  #[must_use]
+  pub fn events(&self) -> Vec<Event> {
+    assert!(
+      flush(FLUSH_TIMEOUT),
+      "PostHog worker did not process pending events in time"
+    );
+    self.events.lock().unwrap_or_else(PoisonError::into_inner).clone()
+  }
+
+  /// Returns the most recently recorded event with the given name.
+  #[must_use]
+  pub fn find(&self, name: &str) -> Option<Event> {
+    self.events().into_iter().rev().find(|event| event.event == name)
+  }
+
+  /// Removes all recorded events.
+  pub fn clear(&self) {
+    self.events.lock().unwrap_or_else(PoisonError::into_inner).clear();
+  }
+
+  /// Asserts that an event with the given name was recorded.
+  ///
+  /// # Panics
+  ///
+  /// Panics if no such event was recorded.
+  #[track_caller]
+  pub fn assert_captured(&self, name: &str) {
+    let events = self.events();
+    assert!(
+      events.iter().any(|event| event.event == name),
+      "expected `{name}` event to be captured, got: {:?}",
+      events.iter().map(|event| event.event.as_str()).collect::<Vec<_>>()
+    );
+  }
+
+  /// Asserts that no event with the given name was recorded.
+  ///
+  /// # Panics
+  ///
+  /// Panics if such an event was recorded.
+  #[track_caller]
+  pub fn assert_not_captured(&self, name: &str) {
+    assert!(self.find(name).is_none(), "expected `{name}` event not to be captured");
+  }
+}
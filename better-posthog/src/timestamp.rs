@@ -0,0 +1,214 @@
+//! Typed event timestamps.
+
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+use time::format_description::well_known::Rfc3339;
+use time::{OffsetDateTime, UtcOffset};
+
+use crate::client::timestamp_precision;
+
+/// An event timestamp, guaranteed to be a valid RFC 3339 date-time.
+///
+/// It can be passed anywhere a timestamp string is accepted,
+/// like [`EventBuilder::timestamp`](crate::EventBuilder::timestamp).
+///
+/// Timestamps generated by the SDK, with [`Timestamp::now`] or from a [`SystemTime`], are in UTC with a fixed number
+/// of fractional second digits, set by the `timestamp_precision` option, e.g. `2024-01-01T12:00:00.000Z`.
+/// Parsed timestamps keep their original offset and precision.
+///
+/// # Examples
+///
+/// ```
+/// use better_posthog::{Event, Timestamp};
+///
+/// let timestamp = Timestamp::parse("2024-01-01T12:00:00Z").unwrap();
+/// let event = Event::builder().event("imported_event").timestamp(timestamp).build();
+///
+/// assert!(Timestamp::parse("yesterday").is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Timestamp(String);
+
+impl Timestamp {
+  /// Returns the current time, with the precision configured at initialization.
+  #[must_use]
+  pub fn now() -> Self {
+    Self::now_with_precision(timestamp_precision())
+  }
+
+  /// Returns the current time with the given precision.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use better_posthog::{Timestamp, TimestampPrecision};
+  ///
+  /// let timestamp = Timestamp::now_with_precision(TimestampPrecision::Micros);
+  /// assert_eq!(timestamp.as_str().len(), "2024-01-01T12:00:00.000000Z".len());
+  /// ```
+  #[must_use]
+  pub fn now_with_precision(precision: TimestampPrecision) -> Self {
+    Self::from_date_time(OffsetDateTime::now_utc(), precision)
+  }
+
+  /// Parses an RFC 3339 date-time, like `2024-01-01T12:00:00Z` or `2024-01-01T14:00:00.5+02:00`.
+  ///
+  /// # Errors
+  ///
+  /// Returns [`ParseTimestampError`] if the input is not a valid RFC 3339 date-time.
+  pub fn parse(input: &str) -> Result<Self, ParseTimestampError> {
+    let date_time = OffsetDateTime::parse(input, &Rfc3339).map_err(ParseTimestampError)?;
+    // Only years outside of 0..=9999 can't be formatted, which can't come from RFC 3339 input.
+    Ok(Self(
+      date_time.format(&Rfc3339).unwrap_or_else(|_| date_time.to_string()),
+    ))
+  }
+
+  /// Returns the timestamp as an RFC 3339 string slice.
+  #[must_use]
+  pub fn as_str(&self) -> &str {
+    &self.0
+  }
+
+  /// Formats the date-time as RFC 3339 in UTC, with the given precision.
+  fn from_date_time(date_time: OffsetDateTime, precision: TimestampPrecision) -> Self {
+    let date_time = date_time.to_offset(UtcOffset::UTC);
+    let (fraction, digits) = match precision {
+      TimestampPrecision::Millis => (u32::from(date_time.millisecond()), 3),
+      TimestampPrecision::Micros => (date_time.microsecond(), 6),
+    };
+    Self(format!(
+      "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{fraction:0digits$}Z",
+      date_time.year(),
+      u8::from(date_time.month()),
+      date_time.day(),
+      date_time.hour(),
+      date_time.minute(),
+      date_time.second(),
+    ))
+  }
+}
+
+impl From<SystemTime> for Timestamp {
+  fn from(time: SystemTime) -> Self {
+    Self::from_date_time(OffsetDateTime::from(time), timestamp_precision())
+  }
+}
+
+impl From<Timestamp> for String {
+  fn from(timestamp: Timestamp) -> Self {
+    timestamp.0
+  }
+}
+
+impl fmt::Display for Timestamp {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(&self.0)
+  }
+}
+
+/// Number of fractional second digits of timestamps generated by the SDK.
+///
+/// # Examples
+///
+/// ```
+/// use better_posthog::TimestampPrecision;
+///
+/// let options = better_posthog::ClientOptions {
+///   api_key: Some("phc_your_api_key".into()),
+///   timestamp_precision: TimestampPrecision::Micros,
+///   ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TimestampPrecision {
+  /// Milliseconds, e.g. `2024-01-01T12:00:00.123Z`.
+  #[default]
+  Millis,
+  /// Microseconds, e.g. `2024-01-01T12:00:00.123456Z`.
+  Micros,
+}
+
+/// Configuration of the guard against event timestamps skewed by misconfigured clocks.
+///
+/// Events whose timestamp is more than `max_future_skew` ahead of the current time, or older than `max_age`,
+/// are handled according to the `policy`, logging a warning. Old timestamps are accepted when importing data
+/// with the `historical_migration` option. Events without a timestamp, or with one that is not a valid RFC 3339
+/// date-time, are not checked.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use better_posthog::{SkewedTimestampPolicy, TimestampGuardConfig};
+///
+/// let options = better_posthog::ClientOptions {
+///   api_key: Some("phc_your_api_key".into()),
+///   timestamp_guard: Some(TimestampGuardConfig {
+///     max_future_skew: Duration::from_secs(5 * 60),
+///     policy: SkewedTimestampPolicy::Drop,
+///     ..Default::default()
+///   }),
+///   ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone)]
+pub struct TimestampGuardConfig {
+  /// Maximum time an event timestamp can be ahead of the current time (default: 1 hour).
+  pub max_future_skew: Duration,
+  /// Maximum age of an event timestamp, unless the `historical_migration` option is set (default: 365 days).
+  pub max_age: Duration,
+  /// What to do with events whose timestamp is out of these bounds (default: [`SkewedTimestampPolicy::Clamp`]).
+  pub policy: SkewedTimestampPolicy,
+}
+
+impl TimestampGuardConfig {
+  /// Returns why the timestamp is out of bounds, if it is, ignoring its age if `historical` is set.
+  pub(crate) fn check(&self, timestamp: &str, historical: bool) -> Option<&'static str> {
+    let date_time = OffsetDateTime::parse(timestamp, &Rfc3339).ok()?;
+    let now = OffsetDateTime::now_utc();
+    if date_time - now > self.max_future_skew {
+      Some("in the future")
+    } else if !historical && now - date_time > self.max_age {
+      Some("too old")
+    } else {
+      None
+    }
+  }
+}
+
+impl Default for TimestampGuardConfig {
+  fn default() -> Self {
+    Self {
+      max_future_skew: Duration::from_hours(1),
+      max_age: Duration::from_hours(365 * 24),
+      policy: SkewedTimestampPolicy::Clamp,
+    }
+  }
+}
+
+/// What to do with events whose timestamp is out of the bounds of the `timestamp_guard` option.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SkewedTimestampPolicy {
+  /// Replace the timestamp with the current time, so the event is still sent.
+  #[default]
+  Clamp,
+  /// Drop the event.
+  Drop,
+}
+
+/// Error returned by [`Timestamp::parse`] when the input is not a valid RFC 3339 date-time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseTimestampError(time::error::Parse);
+
+impl fmt::Display for ParseTimestampError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "invalid RFC 3339 timestamp: {}", self.0)
+  }
+}
+
+impl std::error::Error for ParseTimestampError {}
@@ -0,0 +1,64 @@
+//! Correlation of events with distributed traces.
+
+/// Trace and span IDs attached to events as the `$trace_id` and `$span_id` properties.
+///
+/// It is provided by the `trace_context` option when events are captured, so analytics can be joined with traces.
+///
+/// # Examples
+///
+/// ```
+/// use better_posthog::TraceContext;
+///
+/// let options = better_posthog::ClientOptions {
+///   api_key: Some("phc_your_api_key".into()),
+///   // E.g. read from the OpenTelemetry context of the current span.
+///   trace_context: Some(Box::new(|| {
+///     Some(TraceContext::new("4bf92f3577b34da6a3ce929d0e0e4736", "00f067aa0ba902b7"))
+///   })),
+///   ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TraceContext {
+  /// ID of the trace, set as the `$trace_id` property.
+  pub trace_id: Option<String>,
+  /// ID of the span, set as the `$span_id` property.
+  pub span_id: Option<String>,
+}
+
+impl TraceContext {
+  /// Creates a trace context from the given trace and span IDs.
+  pub fn new(trace_id: impl Into<String>, span_id: impl Into<String>) -> Self {
+    Self {
+      trace_id: Some(trace_id.into()),
+      span_id: Some(span_id.into()),
+    }
+  }
+
+  /// Returns the context of the current [`tracing`] span, or `None` outside of any span.
+  ///
+  /// Only the span ID is set, as 16 hexadecimal digits, since `tracing` spans don't belong to a trace on their own.
+  /// It can be used as the `trace_context` option directly.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use better_posthog::TraceContext;
+  ///
+  /// let options = better_posthog::ClientOptions {
+  ///   api_key: Some("phc_your_api_key".into()),
+  ///   trace_context: Some(Box::new(TraceContext::current)),
+  ///   ..Default::default()
+  /// };
+  /// ```
+  #[cfg(feature = "tracing")]
+  #[must_use]
+  pub fn current() -> Option<Self> {
+    let id = tracing::Span::current().id()?;
+    Some(Self {
+      trace_id: None,
+      span_id: Some(format!("{:016x}", id.into_u64())),
+    })
+  }
+}
@@ -1,279 +1,1704 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::{SyncSender, sync_channel};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
 use std::thread::{self, JoinHandle};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use serde::Serialize;
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender, TrySendError, bounded, never, select_biased, unbounded};
 use serde_json::Value;
 
-use crate::Event;
-use crate::client::ClientOptions;
-use crate::context::saturate_event;
+use crate::circuit_breaker::CircuitBreaker;
+use crate::client::{
+  ApiKey, BeforeEnqueueFn, BeforeSendFn, ClientOptions, Host, OversizedEventPolicy, ShutdownCapturePolicy,
+  ShutdownRemainingFn, TraceContextFn, anonymous_id,
+};
+use crate::context::{self, saturate_event};
+use crate::dedup::DedupWindow;
+use crate::error::Error;
+use crate::http::{BatchResponse, Delivery, HttpClient, PreparedRequest, estimated_size};
+use crate::logging::{LogLevels, log_at};
+use crate::person::merge_person_properties;
+use crate::policy::{self, EventPolicy};
+use crate::retry::{RetryConfig, RetryPolicy};
+use crate::spool::{RecordId, Spool};
+use crate::stats::{Counters, Stats};
+use crate::{Attachment, Event, SkewedTimestampPolicy, Timestamp, events};
+
+/// Maximum number of tasks waiting in the queue, beyond which new events are dropped.
+pub const QUEUE_CAPACITY: usize = 256;
+
+/// Property used by PostHog to deduplicate events.
+const INSERT_ID: &str = "$insert_id";
+
+/// Property telling PostHog whether to create or update a person profile for an event.
+const PROCESS_PERSON_PROFILE: &str = "$process_person_profile";
+
+/// Property of the session end event holding the session duration in seconds.
+const SESSION_DURATION: &str = "$session_duration";
+
+/// Name of the worker thread, and of the threads sending concurrent requests.
+pub const THREAD_NAME: &str = "better-posthog-worker";
 
 /// Messages that can be sent to the worker thread.
 enum Task {
-  /// A single event to capture.
-  Capture(Event),
-  /// A batch of events to send together.
-  Batch(Vec<Event>),
+  /// A single event to capture, optionally sent to a different destination, with its spool record if spooled.
+  Capture(Event, Option<Destination>, Option<RecordId>),
+  /// A single event to capture, with attachments to upload first.
+  CaptureWithAttachments(Event, Vec<Attachment>),
+  /// A batch of events to send together, with its spool record if spooled.
+  Batch(Vec<Event>, Option<RecordId>),
+  /// A batch of events to send together in a single request, with a channel receiving PostHog's response.
+  BatchWithResponse(Vec<Event>, Sender<Result<BatchResponse, Error>>),
+  /// A pre-serialized event object, sent as is.
+  Raw(Value),
+}
+
+impl Task {
+  /// Returns the number of events in the task.
+  const fn events_count(&self) -> usize {
+    match self {
+      Self::Batch(events, _) | Self::BatchWithResponse(events, _) => events.len(),
+      Self::Capture(..) | Self::CaptureWithAttachments(..) | Self::Raw(_) => 1,
+    }
+  }
+
+  /// Returns the events of the task to spool with the slot of their record, if they weren't spooled yet.
+  ///
+  /// Events sent to a different destination, with attachments, or awaiting PostHog's response, and raw events,
+  /// are not spooled.
+  fn unspooled(&mut self) -> Option<(&[Event], &mut Option<RecordId>)> {
+    match self {
+      Self::Capture(event, None, record @ None) => Some((std::slice::from_ref(event), record)),
+      Self::Batch(events, record @ None) => Some((events, record)),
+      Self::Capture(..)
+      | Self::CaptureWithAttachments(..)
+      | Self::Batch(..)
+      | Self::BatchWithResponse(..)
+      | Self::Raw(_) => None,
+    }
+  }
+
+  /// Returns the spool record of the task, if it was spooled.
+  const fn spool_record(&self) -> Option<RecordId> {
+    match self {
+      Self::Capture(_, _, record) | Self::Batch(_, record) => *record,
+      Self::CaptureWithAttachments(..) | Self::BatchWithResponse(..) | Self::Raw(_) => None,
+    }
+  }
+}
+
+/// Host and API key overriding the client configuration for a single event.
+pub struct Destination {
+  pub host: Host,
+  pub api_key: ApiKey,
+}
+
+/// Request to PostHog prepared by the worker, ready to be delivered.
+enum Request {
+  /// A single event, optionally sent to a different destination.
+  Capture(Event, Option<Destination>),
+  /// A batch of events, optionally sent to a different destination.
+  Batch(Vec<Event>, Option<Destination>),
+  /// A pre-serialized event object.
+  Raw(Value),
+}
+
+impl Request {
+  /// Returns the number of events in the request.
+  const fn events_count(&self) -> usize {
+    match self {
+      Self::Batch(events, _) => events.len(),
+      Self::Capture(..) | Self::Raw(_) => 1,
+    }
+  }
+
+  /// Returns the destination of the request, if it differs from the configured one.
+  const fn destination(&self) -> Option<&Destination> {
+    match self {
+      Self::Capture(_, destination) | Self::Batch(_, destination) => destination.as_ref(),
+      Self::Raw(_) => None,
+    }
+  }
+}
+
+/// Lifecycle transitions of the background worker, reported to the `lifecycle_hook` option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WorkerLifecycle {
+  /// The worker thread started and is ready to process events.
+  Started,
+  /// The worker started processing a flush request.
+  Flushing,
+  /// The worker started sending the remaining events before stopping, when the [`ClientGuard`](crate::ClientGuard) is dropped.
+  Draining,
+  /// The worker stopped sending events and keeps them queued, see [`events::pause`](crate::events::pause).
+  Paused,
+  /// The worker resumed sending events, see [`events::resume`](crate::events::resume).
+  Resumed,
+  /// The worker thread exited after idling for the `idle_timeout` option; it is respawned on the next capture.
+  Idle,
+  /// The worker thread stopped; no more events will be sent.
+  Stopped,
+  /// The worker thread panicked; it is respawned on the next capture if the `respawn_on_panic` option is set.
+  Panicked,
+}
+
+/// Control messages that bypass the bounded event queue.
+enum Control {
   /// Flush request with acknowledgment channel.
-  Flush(SyncSender<()>),
+  Flush(Sender<()>),
+  /// Request to send the remaining events and stop, with acknowledgment channel and the deadline of the drain.
+  Drain(Sender<()>, Instant),
+  /// Request to stop processing queued events until resumed.
+  Pause,
+  /// Request to resume processing queued events.
+  Resume,
+  /// Request to append a `before_send` hook.
+  AddBeforeSend(BeforeSendFn),
   /// Shutdown signal.
   Shutdown,
 }
 
-/// Payload for single event capture (`/i/v0/e/`).
-#[derive(Serialize)]
-struct CapturePayload<'a> {
-  api_key: &'a str,
-  event: &'a str,
-  distinct_id: &'a str,
-  properties: &'a HashMap<String, Value>,
-  #[serde(skip_serializing_if = "Option::is_none")]
-  timestamp: Option<&'a str>,
+/// Number of tasks enqueued but not processed yet, to wait for the worker to become idle.
+#[derive(Default)]
+struct Pending {
+  count: AtomicUsize,
+  /// Number of events in these tasks.
+  events: AtomicUsize,
+  lock: Mutex<()>,
+  /// Notified when the count drops to zero.
+  idle: Condvar,
 }
 
-/// Payload for batch event capture (`/batch/`).
-#[derive(Serialize)]
-struct BatchPayload<'a> {
-  api_key: &'a str,
-  batch: Vec<BatchEvent>,
+impl Pending {
+  /// Counts a task with the given number of events about to be enqueued.
+  fn add(&self, events: usize) {
+    self.events.fetch_add(events, Ordering::SeqCst);
+    self.count.fetch_add(1, Ordering::SeqCst);
+  }
+
+  /// Marks tasks with the given total number of events as processed, waking up waiters if none are left.
+  fn complete(&self, tasks: usize, events: usize) {
+    self.events.fetch_sub(events, Ordering::SeqCst);
+    if tasks > 0 && self.count.fetch_sub(tasks, Ordering::SeqCst) == tasks {
+      let _guard = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+      self.idle.notify_all();
+    }
+  }
+
+  /// Waits until all tasks are processed, up to the specified timeout. Returns `true` if they were.
+  fn wait(&self, timeout: Duration) -> bool {
+    let guard = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+    !self
+      .idle
+      .wait_timeout_while(guard, timeout, |()| self.count.load(Ordering::SeqCst) > 0)
+      .unwrap_or_else(PoisonError::into_inner)
+      .1
+      .timed_out()
+  }
 }
 
-/// Single event within a batch. Note: `distinct_id` goes inside `properties`.
-#[derive(Serialize)]
-struct BatchEvent {
-  event: String,
-  properties: HashMap<String, Value>,
-  #[serde(skip_serializing_if = "Option::is_none")]
-  timestamp: Option<String>,
+/// Delivery mechanism used by the worker.
+pub enum Transport {
+  /// Sends events to PostHog over HTTP.
+  Http(HttpClient),
+  /// Records events in memory instead of sending them.
+  #[cfg(feature = "test-util")]
+  Memory(Arc<std::sync::Mutex<Vec<Event>>>),
 }
 
 /// Background worker thread for sending events to PostHog.
 pub struct Worker {
-  sender: SyncSender<Task>,
+  sender: Sender<Task>,
+  control: Sender<Control>,
   shutdown: Arc<AtomicBool>,
-  handle: Option<JoinHandle<()>>,
+  /// Whether the worker is being drained, after which new events are handled by the `shutdown_capture_policy`.
+  drained: AtomicBool,
+  /// Whether the drain completed or timed out, after which new events are ignored.
+  closed: AtomicBool,
+  shutdown_capture_policy: ShutdownCapturePolicy,
+  log_levels: LogLevels,
+  /// Whether events without a timestamp are stamped when they are enqueued.
+  auto_timestamp: bool,
+  /// Whether events with an invalid timestamp are dropped when they are enqueued.
+  strict_timestamps: bool,
+  /// Policies dropping or sampling events by name before they are enqueued.
+  event_policies: Vec<(String, EventPolicy)>,
+  /// Hooks applied to events on the calling thread before they are enqueued.
+  before_enqueue: Vec<BeforeEnqueueFn>,
+  /// Function providing the trace context of captured events.
+  trace_context: Option<TraceContextFn>,
+  /// Event captured when the worker is drained, with the session duration.
+  session_end_event: Mutex<Option<Event>>,
+  /// Time the worker was created, when the session started.
+  started: Instant,
+  counters: Arc<Counters>,
+  pending: Arc<Pending>,
+  /// Spool of the queued events, shared with the worker thread.
+  spool: Option<Arc<Mutex<Spool>>>,
+  /// Receivers of the queues, kept to respawn the worker thread after it exits while idle.
+  receiver: Receiver<Task>,
+  control_receiver: Receiver<Control>,
+  /// Whether the worker thread exited while idle and must be respawned.
+  idle: Arc<AtomicBool>,
+  /// Handle of the worker thread, returning its state if it exited while idle.
+  handle: Mutex<Option<JoinHandle<Option<WorkerState>>>>,
+  /// Workers of the configured mirrors, receiving a copy of the events processed by this worker.
+  mirrors: Vec<Arc<Self>>,
 }
 
 impl Worker {
   /// Creates a new worker with a background thread for sending events.
   pub fn new(mut options: ClientOptions) -> Self {
-    let (sender, receiver) = sync_channel(256);
-    let shutdown = Arc::new(AtomicBool::new(false));
-
-    let handle = thread::Builder::new()
-      .name("better-posthog-worker".into())
-      .spawn({
-        let shutdown = shutdown.clone();
+    let http_client = HttpClient::new(&mut options);
+    Self::with_transport(options, Transport::Http(http_client))
+  }
 
-        let http_client = reqwest::blocking::Client::new();
-        move || {
-          for task in receiver {
-            if shutdown.load(Ordering::SeqCst) {
-              return;
-            }
+  /// Creates a new worker that delivers events with the given transport.
+  pub fn with_transport(options: ClientOptions, transport: Transport) -> Self {
+    Self::build(options, transport, true)
+  }
 
-            match task {
-              Task::Capture(mut event) => {
-                log::trace!("Processing capture task for event: {}", event.event);
-                saturate_event(&mut event);
-                if let Some(event) = apply_before_send(&mut options, event) {
-                  send_capture(&http_client, &options, &event);
-                } else {
-                  log::trace!("Event was dropped by before_send hook");
-                }
-              }
-              Task::Batch(events) => {
-                let events_count = events.len();
-                log::trace!("Processing batch task with {events_count} events");
-
-                let events: Vec<Event> = events
-                  .into_iter()
-                  .filter_map(|mut event| {
-                    saturate_event(&mut event);
-                    apply_before_send(&mut options, event)
-                  })
-                  .collect();
-                if events_count != events.len() {
-                  log::trace!(
-                    "{} events were dropped by before_send hook",
-                    events_count - events.len()
-                  );
-                }
+  /// Creates the worker of a mirror, whose counters are not exported through the `metrics` crate.
+  fn mirror(mut options: ClientOptions) -> Self {
+    let http_client = HttpClient::new(&mut options);
+    Self::build(options, Transport::Http(http_client), false)
+  }
 
-                if !events.is_empty() {
-                  send_batch(&http_client, &options, &events);
-                }
-              }
-              Task::Flush(sender) => {
-                log::trace!("Processing flush task");
-                sender.send(()).ok();
-              }
-              Task::Shutdown => {
-                log::trace!("Shutting down worker thread");
-                return;
-              }
-            }
-          }
-        }
-      })
-      .ok();
+  /// Creates a worker delivering events with the given transport, exporting its counters if `export_metrics` is set
+  /// and the `metrics` feature is enabled.
+  fn build(mut options: ClientOptions, transport: Transport, export_metrics: bool) -> Self {
+    let (sender, receiver) = bounded(QUEUE_CAPACITY);
+    let (control, control_receiver) = unbounded();
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let counters = Arc::new(Counters::new(export_metrics));
+    let pending = Arc::new(Pending::default());
+    let spool = options
+      .spool_path
+      .clone()
+      .map(|path| Arc::new(Mutex::new(Spool::new(path))));
+    let log_levels = options.log_levels;
+    let auto_timestamp = options.auto_timestamp;
+    let strict_timestamps = options.strict_timestamps;
+    let event_policies = std::mem::take(&mut options.event_policies);
+    let shutdown_capture_policy = options.shutdown_capture_policy;
+    let before_enqueue = std::mem::take(&mut options.before_enqueue);
+    let trace_context = options.trace_context.take();
+    let session_end_event = Mutex::new(options.session_end_event.take());
+    let mirrors: Vec<Arc<Self>> = std::mem::take(&mut options.mirrors)
+      .iter()
+      .map(|mirror| Arc::new(Self::mirror(mirror.options(&options))))
+      .collect();
+    let state = WorkerState {
+      circuit_breaker: options.circuit_breaker.clone().map(CircuitBreaker::new),
+      dedup: options.dedup_window.map(DedupWindow::new),
+      spool: spool.clone(),
+      auth_disabled: false,
+      context: context::context(&options),
+      options,
+      transport,
+      counters: counters.clone(),
+      pending: pending.clone(),
+      mirrors: mirrors.clone(),
+    };
 
-    Self {
+    let mut worker = Self {
       sender,
+      control,
       shutdown,
-      handle,
+      drained: AtomicBool::new(false),
+      closed: AtomicBool::new(false),
+      shutdown_capture_policy,
+      log_levels,
+      auto_timestamp,
+      strict_timestamps,
+      event_policies,
+      before_enqueue,
+      trace_context,
+      session_end_event,
+      started: Instant::now(),
+      counters,
+      pending,
+      spool,
+      receiver,
+      control_receiver,
+      idle: Arc::new(AtomicBool::new(false)),
+      handle: Mutex::new(None),
+      mirrors,
+    };
+    let handle = worker.spawn(state);
+    *worker.handle.get_mut().unwrap_or_else(PoisonError::into_inner) = handle;
+    worker.resend_spooled();
+    worker
+  }
+
+  /// Enqueues the events spooled by a previous run that crashed before handling them.
+  fn resend_spooled(&self) {
+    let Some((record, events)) = self
+      .spool
+      .as_ref()
+      .and_then(|spool| spool.lock().unwrap_or_else(PoisonError::into_inner).recover())
+    else {
+      return;
+    };
+    let events_count = events.len();
+    log::info!("Resending {events_count} PostHog events spooled by a previous run");
+    if let Err(e) = self.try_send(Task::Batch(events, Some(record))) {
+      log_at!(self.log_levels.queue_drop, "PostHog spooled events dropped: {e}");
+      self.counters.add_dropped(events_count);
+    } else {
+      self.counters.add_captured(events_count);
+    }
+  }
+
+  /// Spawns the worker thread running the given state.
+  fn spawn(&self, state: WorkerState) -> Option<JoinHandle<Option<WorkerState>>> {
+    let receiver = self.receiver.clone();
+    let control_receiver = self.control_receiver.clone();
+    let shutdown = self.shutdown.clone();
+    let idle = self.idle.clone();
+    thread::Builder::new()
+      .name(THREAD_NAME.into())
+      .spawn(move || state.run(&receiver, &control_receiver, &shutdown, &idle))
+      .inspect_err(|e| log::error!("Failed to spawn PostHog worker thread: {e}"))
+      .ok()
+  }
+
+  /// Respawns the worker thread if it exited while idle, so that it processes the message just sent to it.
+  fn wake(&self) {
+    if !self.idle.load(Ordering::SeqCst)
+      || self
+        .idle
+        .compare_exchange(true, false, Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+    {
+      return;
+    }
+
+    let mut handle = self.handle.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(Ok(Some(state))) = handle.take().map(JoinHandle::join) {
+      log::trace!("Respawning idle worker thread");
+      *handle = self.spawn(state);
+    } else {
+      log::error!("Failed to respawn PostHog worker thread");
+    }
+  }
+
+  /// Sends a single event to PostHog, returning `true` if it was enqueued.
+  ///
+  /// If the queue is full, the event is dropped and logged.
+  pub fn capture(&self, event: Event, destination: Option<Destination>) -> bool {
+    !self.is_drained(1) && self.enqueue(event, destination)
+  }
+
+  /// Enqueues a task without blocking, counting it as pending until the worker processes it.
+  ///
+  /// Its events are spooled, if enabled, until the worker handles them.
+  /// On failure, the task is dropped, and [`Error::QueueFull`] or [`Error::WorkerGone`] is returned.
+  fn try_send(&self, mut task: Task) -> Result<(), Error> {
+    if self.is_gone() {
+      return Err(Error::WorkerGone);
     }
+    let events_count = task.events_count();
+    let spooled = self.spool.as_ref().and_then(|spool| {
+      let (events, record) = task.unspooled()?;
+      let buffer = Spool::encode(events)?;
+      // The spool stays locked until the record is appended, so the worker can't remove it before.
+      let mut spool = spool.lock().unwrap_or_else(PoisonError::into_inner);
+      let id = spool.reserve();
+      *record = Some(id);
+      Some((spool, id, buffer))
+    });
+    self.pending.add(events_count);
+    let result = self.sender.try_send(task).map_err(|e| {
+      self.pending.complete(1, events_count);
+      match e {
+        TrySendError::Full(_) => Error::QueueFull,
+        TrySendError::Disconnected(_) => Error::WorkerGone,
+      }
+    });
+    if let Some((mut spool, id, buffer)) = spooled
+      && result.is_ok()
+    {
+      spool.append(id, buffer);
+    }
+    #[cfg(feature = "metrics")]
+    self.counters.export_queue_depth(self.sender.len());
+    result
+  }
+
+  /// Returns `true` if the worker thread panicked and was not respawned.
+  fn is_gone(&self) -> bool {
+    self.counters.worker_gone.load(Ordering::Relaxed)
   }
 
-  /// Sends a single event to PostHog.
+  /// Sends a single event with attachments to PostHog, after uploading them.
   ///
-  /// If the queue is full, the event is dropped and a warning is logged.
-  pub fn capture(&self, event: Event) {
+  /// If the queue is full, the event is dropped and logged.
+  pub fn capture_with_attachments(&self, event: Event, attachments: Vec<Attachment>) {
+    if self.is_drained(1) {
+      return;
+    }
+    let Some(event) = self.prepare(event) else {
+      return;
+    };
+    log::trace!("Capturing {} event with {} attachments", event.event, attachments.len());
+    self.enqueue_capture(Task::CaptureWithAttachments(event, attachments));
+  }
+
+  /// Enqueues a single event, even if the worker is being drained, returning `true` if it was enqueued.
+  fn enqueue(&self, event: Event, destination: Option<Destination>) -> bool {
+    let Some(event) = self.prepare(event) else {
+      return false;
+    };
     log::trace!("Capturing {} event", event.event);
-    if let Err(e) = self.sender.try_send(Task::Capture(event)) {
-      log::warn!("PostHog event dropped: {e}");
+    self.enqueue_capture(Task::Capture(event, destination, None))
+  }
+
+  /// Enqueues a task capturing a single prepared event, counting it as captured or dropped.
+  ///
+  /// Returns `true` if it was enqueued.
+  fn enqueue_capture(&self, task: Task) -> bool {
+    if let Err(e) = self.try_send(task) {
+      log_at!(self.log_levels.queue_drop, "PostHog event dropped: {e}");
+      self.counters.add_dropped(1);
+      return false;
+    }
+    self.counters.add_captured(1);
+    self.wake();
+    true
+  }
+
+  /// Sends a pre-serialized event object to PostHog.
+  ///
+  /// If the queue is full, the event is dropped and logged.
+  pub fn capture_raw(&self, event: Value) {
+    if self.is_drained(1) {
+      return;
+    }
+    log::trace!("Capturing raw event");
+    if let Err(e) = self.try_send(Task::Raw(event)) {
+      log_at!(self.log_levels.queue_drop, "PostHog raw event dropped: {e}");
+      self.counters.add_dropped(1);
+    } else {
+      self.counters.add_captured(1);
+      self.wake();
     }
   }
 
   /// Sends a batch of events to PostHog.
   ///
-  /// If the queue is full, the batch is dropped and a warning is logged.
+  /// If the queue is full, the batch is dropped and logged.
   pub fn batch(&self, events: Vec<Event>) {
-    log::trace!("Capturing batch with {} events", events.len());
-    if let Err(e) = self.sender.try_send(Task::Batch(events)) {
-      log::warn!("PostHog batch dropped: {e}");
+    if self.is_drained(events.len()) {
+      return;
+    }
+    let events: Vec<Event> = events.into_iter().filter_map(|event| self.prepare(event)).collect();
+    let events_count = events.len();
+    log::trace!("Capturing batch with {events_count} events");
+    if let Err(e) = self.try_send(Task::Batch(events, None)) {
+      log_at!(self.log_levels.queue_drop, "PostHog batch dropped: {e}");
+      self.counters.add_dropped(events_count);
+    } else {
+      self.counters.add_captured(events_count);
+      self.wake();
+    }
+  }
+
+  /// Sends a batch of events to PostHog in a single request, waiting up to the specified timeout for the response.
+  ///
+  /// The batch is queued behind pending events, and isn't split, spooled, or retried.
+  pub fn batch_blocking(&self, events: Vec<Event>, timeout: Duration) -> Result<BatchResponse, Error> {
+    if self.is_drained(events.len()) {
+      return Err(Error::Drained);
+    }
+    if self.is_gone() {
+      return Err(Error::WorkerGone);
+    }
+    let events: Vec<Event> = events.into_iter().filter_map(|event| self.prepare(event)).collect();
+    let deadline = Instant::now() + timeout;
+    let events_count = events.len();
+    log::trace!("Capturing blocking batch with {events_count} events");
+    let (sender, receiver) = bounded(1);
+    self.pending.add(events_count);
+    if let Err(e) = self
+      .sender
+      .send_deadline(Task::BatchWithResponse(events, sender), deadline)
+    {
+      self.pending.complete(1, events_count);
+      log_at!(self.log_levels.queue_drop, "PostHog batch dropped: {e}");
+      self.counters.add_dropped(events_count);
+      return Err(if e.is_timeout() {
+        Error::QueueFull
+      } else {
+        Error::Drained
+      });
+    }
+    self.counters.add_captured(events_count);
+    #[cfg(feature = "metrics")]
+    self.counters.export_queue_depth(self.sender.len());
+    self.wake();
+    match receiver.recv_deadline(deadline) {
+      Ok(result) => result,
+      Err(RecvTimeoutError::Timeout) => Err(Error::Timeout),
+      Err(RecvTimeoutError::Disconnected) if self.is_gone() => Err(Error::WorkerGone),
+      Err(RecvTimeoutError::Disconnected) => Err(Error::Drained),
     }
   }
 
-  /// Flushes pending events, waiting up to the specified timeout.
+  /// Flushes pending events, then the mirrors, waiting up to the specified timeout.
   ///
-  /// Returns `true` if the flush completed within the timeout.
+  /// Returns `true` if the flush completed within the timeout. Mirrors that time out are logged, but don't
+  /// affect the result.
   pub fn flush(&self, timeout: Duration) -> bool {
     log::trace!("Flushing event with {timeout:?} timeout");
-    let (sender, receiver) = sync_channel(1);
-    let _ = self.sender.send(Task::Flush(sender));
-    receiver.recv_timeout(timeout).is_ok()
+    let deadline = Instant::now() + timeout;
+    let (sender, receiver) = bounded(1);
+    let _ = self.control.send(Control::Flush(sender));
+    self.wake();
+    let flushed = receiver.recv_timeout(timeout).is_ok();
+    for (index, mirror) in self.mirrors.iter().enumerate() {
+      if !mirror.flush(deadline.saturating_duration_since(Instant::now())) {
+        log::debug!("PostHog mirror {index} flush timed out");
+      }
+    }
+    flushed
+  }
+
+  /// Removes the events waiting in the queue without sending them, and returns them.
+  ///
+  /// Events the worker thread already took from the queue are still sent.
+  pub fn take_queued(&self) -> Vec<Event> {
+    let (events, tasks_count, events_count) = take_queued(&self.receiver, self.spool.as_deref());
+    self.pending.complete(tasks_count, events_count);
+    #[cfg(feature = "metrics")]
+    self.counters.export_queue_depth(self.receiver.len());
+    log::trace!("Took {} events from the queue", events.len());
+    events
+  }
+
+  /// Returns the number of events enqueued but not processed yet, including the ones being sent.
+  pub fn pending_events(&self) -> usize {
+    self.pending.events.load(Ordering::SeqCst)
+  }
+
+  /// Sends the remaining events and stops the worker, then the mirrors, waiting up to the specified timeout.
+  ///
+  /// The session end event, if configured, is captured first.
+  /// Returns `true` if all events were processed within the timeout. Mirrors that time out are logged, but don't
+  /// affect the result.
+  /// Events captured meanwhile are handled by the `shutdown_capture_policy`, and the ones captured afterwards
+  /// are ignored. Subsequent calls return `true` immediately.
+  pub fn drain(&self, timeout: Duration) -> bool {
+    if self.drained.swap(true, Ordering::SeqCst) {
+      return true;
+    }
+    let session_end_event = self
+      .session_end_event
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .take();
+    if let Some(mut event) = session_end_event {
+      event.insert_property(SESSION_DURATION, self.started.elapsed().as_secs_f64());
+      self.enqueue(event, None);
+    }
+    log::trace!("Draining events with {timeout:?} timeout");
+    let deadline = Instant::now() + timeout;
+    let (sender, receiver) = bounded(1);
+    let _ = self.control.send(Control::Drain(sender, deadline));
+    self.wake();
+    let drained = receiver.recv_timeout(timeout).is_ok();
+    self.closed.store(true, Ordering::SeqCst);
+    if drained {
+      // Events captured while the worker was finishing the drain are left in the queue.
+      let remaining = self.take_queued().len();
+      if remaining > 0 {
+        log_at!(
+          self.log_levels.queue_drop,
+          "{remaining} PostHog events captured at the end of shutdown were dropped"
+        );
+        self.counters.add_dropped(remaining);
+      }
+    }
+    for (index, mirror) in self.mirrors.iter().enumerate() {
+      if !mirror.drain(deadline.saturating_duration_since(Instant::now())) {
+        log::warn!("PostHog mirror {index} shutdown timed out, some mirrored events may be lost");
+      }
+    }
+    drained
+  }
+
+  /// Waits until all enqueued events are processed and no request is in flight, up to the specified timeout.
+  ///
+  /// Returns `true` if the worker became idle within the timeout. Unlike [`Worker::flush`], this includes events
+  /// enqueued while waiting.
+  pub fn wait_idle(&self, timeout: Duration) -> bool {
+    log::trace!("Waiting for worker to become idle with {timeout:?} timeout");
+    self.pending.wait(timeout)
+  }
+
+  /// Stops sending events, keeping new ones queued until [`Worker::resume`] is called.
+  pub fn pause(&self) {
+    log::trace!("Pausing worker");
+    let _ = self.control.send(Control::Pause);
+    self.wake();
+  }
+
+  /// Resumes sending events, starting with the ones queued while paused.
+  pub fn resume(&self) {
+    log::trace!("Resuming worker");
+    let _ = self.control.send(Control::Resume);
+    self.wake();
+  }
+
+  /// Prepares an event on the calling thread before it is enqueued, returning `None` if it was discarded.
+  ///
+  /// Applies the `event_policies`, sets its distinct ID from the current
+  /// [`with_distinct_id`](crate::events::with_distinct_id) scope and its timestamp to the current time, if enabled
+  /// and not already set, checks its timestamp, attaches the current trace context, and applies the `before_enqueue`
+  /// hooks.
+  /// Discarded events are counted as captured and filtered, like the ones discarded by `before_send` hooks.
+  fn prepare(&self, event: Event) -> Option<Event> {
+    let Some(mut event) = policy::apply(&self.event_policies, event) else {
+      self.counters.add_captured(1);
+      Counters::add(&self.counters.filtered, 1);
+      return None;
+    };
+    if event.distinct_id.is_none() {
+      event.distinct_id = events::scoped_distinct_id();
+    }
+    if self.auto_timestamp {
+      event.timestamp.get_or_insert_with(|| Timestamp::now().into());
+    }
+    if let Some(timestamp) = &event.timestamp
+      && let Err(e) = Timestamp::parse(timestamp)
+    {
+      if self.strict_timestamps {
+        log::warn!("Dropping {} event with {e}", event.event);
+        self.counters.add_captured(1);
+        Counters::add(&self.counters.filtered, 1);
+        return None;
+      }
+      log::warn!("{} event has {e}, PostHog may reject it", event.event);
+    }
+    if let Some(trace_context) = &self.trace_context {
+      match std::panic::catch_unwind(std::panic::AssertUnwindSafe(trace_context)) {
+        Ok(Some(context)) => {
+          for (key, id) in [("$trace_id", context.trace_id), ("$span_id", context.span_id)] {
+            if let Some(id) = id {
+              event.properties.entry(key.to_string()).or_insert(Value::String(id));
+            }
+          }
+        }
+        Ok(None) => {}
+        Err(_) => log::error!("Panic in trace_context hook, capturing event without trace context"),
+      }
+    }
+    for hook in &self.before_enqueue {
+      match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| hook(event))) {
+        Ok(Some(e)) => {
+          event = e;
+          continue;
+        }
+        Ok(None) => log::trace!("Event was dropped by before_enqueue hook"),
+        Err(_) => log::error!("Panic in before_enqueue hook, discarding event"),
+      }
+      self.counters.add_captured(1);
+      Counters::add(&self.counters.filtered, 1);
+      return None;
+    }
+    Some(event)
+  }
+
+  /// Appends a `before_send` hook, applied to the events processed after it is received.
+  pub fn add_before_send(&self, hook: BeforeSendFn) {
+    log::trace!("Adding before_send hook");
+    let _ = self.control.send(Control::AddBeforeSend(hook));
+    self.wake();
+  }
+
+  /// Returns `true` if new events must be ignored because the worker was drained, logging it.
+  ///
+  /// While the worker is being drained, the given number of events is counted as dropped,
+  /// unless the `shutdown_capture_policy` includes them in the drain.
+  fn is_drained(&self, events_count: usize) -> bool {
+    if !self.drained.load(Ordering::SeqCst) {
+      return false;
+    }
+    if self.closed.load(Ordering::SeqCst) {
+      log::trace!("PostHog client is shut down, ignoring event");
+      return true;
+    }
+    match self.shutdown_capture_policy {
+      ShutdownCapturePolicy::Include => false,
+      ShutdownCapturePolicy::Reject => {
+        log_at!(
+          self.log_levels.queue_drop,
+          "PostHog event captured during shutdown dropped"
+        );
+        self.counters.add_dropped(events_count);
+        true
+      }
+    }
+  }
+
+  /// Returns how full the queue is, from `0.0` (empty) to `1.0` (full).
+  pub fn queue_pressure(&self) -> f64 {
+    let to_f64 = |count: usize| f64::from(u32::try_from(count).unwrap_or(u32::MAX));
+    to_f64(self.sender.len()) / to_f64(QUEUE_CAPACITY)
+  }
+
+  /// Returns a snapshot of the worker's runtime counters.
+  pub fn stats(&self) -> Stats {
+    self.counters.snapshot(self.sender.len())
+  }
+
+  /// Returns a snapshot of the runtime counters of each mirror, in the order they were configured.
+  pub fn mirror_stats(&self) -> Vec<Stats> {
+    self.mirrors.iter().map(|mirror| mirror.stats()).collect()
   }
 }
 
 impl Drop for Worker {
   fn drop(&mut self) {
     self.shutdown.store(true, Ordering::SeqCst);
-    let _ = self.sender.send(Task::Shutdown);
-    if let Some(handle) = self.handle.take() {
+    let _ = self.control.send(Control::Shutdown);
+    if let Some(handle) = self.handle.get_mut().unwrap_or_else(PoisonError::into_inner).take() {
       handle.join().ok();
     }
   }
 }
 
-/// Applies all `before_send` hooks to an event.
-///
-/// Returns `Some(event)` if the event should be sent, `None` if it was discarded.
-/// The event is discarded on panic.
-fn apply_before_send(options: &mut ClientOptions, mut event: Event) -> Option<Event> {
-  for hook in &mut options.before_send {
-    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| hook(event))) {
-      Ok(Some(e)) => event = e,
-      Ok(None) => return None,
+/// State owned by the worker thread.
+struct WorkerState {
+  options: ClientOptions,
+  /// Library and OS context properties added to events.
+  context: Cow<'static, HashMap<String, Value>>,
+  transport: Transport,
+  counters: Arc<Counters>,
+  pending: Arc<Pending>,
+  circuit_breaker: Option<CircuitBreaker>,
+  /// Insert IDs of the recent events, to drop duplicates.
+  dedup: Option<DedupWindow>,
+  spool: Option<Arc<Mutex<Spool>>>,
+  /// Whether sending is disabled because PostHog rejected the configured API key.
+  auth_disabled: bool,
+  /// Workers of the configured mirrors, forwarded the processed events without blocking.
+  mirrors: Vec<Arc<Worker>>,
+}
+
+impl WorkerState {
+  /// Runs the worker until shutdown, reporting lifecycle transitions.
+  ///
+  /// Returns the state if the worker exited while idle, or panicked with the `respawn_on_panic` option set,
+  /// to be respawned with it. If it panicked otherwise, the worker is marked as gone.
+  fn run(
+    mut self,
+    receiver: &Receiver<Task>,
+    control: &Receiver<Control>,
+    shutdown: &AtomicBool,
+    idle: &AtomicBool,
+  ) -> Option<Self> {
+    self.notify(WorkerLifecycle::Started);
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+      self.run_loop(receiver, control, shutdown, idle)
+    })) {
+      Ok(true) => {
+        self.notify(WorkerLifecycle::Idle);
+        Some(self)
+      }
+      Ok(false) => {
+        self.notify(WorkerLifecycle::Stopped);
+        None
+      }
+      Err(_) if self.options.respawn_on_panic => {
+        log::error!("PostHog worker thread panicked, respawning it on the next capture");
+        self.notify(WorkerLifecycle::Panicked);
+        idle.store(true, Ordering::SeqCst);
+        Some(self)
+      }
       Err(_) => {
-        log::error!("Panic in before_send hook, discarding event");
-        return None;
+        log::error!("PostHog worker thread panicked, no more events will be sent");
+        self.counters.worker_gone.store(true, Ordering::Relaxed);
+        self.notify(WorkerLifecycle::Panicked);
+        None
       }
     }
   }
-  Some(event)
-}
 
-/// Sends a single event to PostHog via `/i/v0/e/`.
-fn send_capture(client: &reqwest::blocking::Client, options: &ClientOptions, event: &Event) {
-  let url = options.host.capture_url();
-  let payload = CapturePayload {
-    api_key: options.api_key.as_ref().expect("API key must be present").as_str(),
-    event: &event.event,
-    distinct_id: &event.distinct_id,
-    properties: &event.properties,
-    timestamp: event.timestamp.as_deref(),
-  };
+  /// Runs the worker loop until shutdown or until all senders are dropped.
+  ///
+  /// Control messages are polled alongside the event queue and take priority over it,
+  /// so neither flush nor shutdown requests are ever stuck behind a full queue.
+  /// While paused, only control messages are processed and events stay in the queue.
+  ///
+  /// Returns `true` if the worker exited after idling for the `idle_timeout` option, which never happens while paused.
+  fn run_loop(
+    &mut self,
+    receiver: &Receiver<Task>,
+    control: &Receiver<Control>,
+    shutdown: &AtomicBool,
+    idle: &AtomicBool,
+  ) -> bool {
+    let paused_receiver = never();
+    let mut paused = false;
+    loop {
+      if shutdown.load(Ordering::SeqCst) {
+        return false;
+      }
+      let tasks = if paused { &paused_receiver } else { receiver };
+      let idle_timeout = self.options.idle_timeout.filter(|_| !paused).unwrap_or(Duration::MAX);
+      select_biased! {
+        recv(control) -> message => {
+          let Ok(message) = message else { return false };
+          match message {
+            Control::Flush(_) if paused => {
+              // Dropping the acknowledgment channel makes the flush fail immediately.
+              log::trace!("Ignoring flush task while paused");
+            }
+            Control::Flush(sender) => {
+              log::trace!("Processing flush task");
+              self.notify(WorkerLifecycle::Flushing);
+              if !self.process_pending(receiver, shutdown) {
+                return false;
+              }
+              sender.send(()).ok();
+            }
+            Control::Drain(sender, deadline) => {
+              log::trace!("Draining worker thread");
+              self.notify(WorkerLifecycle::Draining);
+              if let Some(hook) = self.options.on_shutdown_remaining.take() {
+                hand_over_pending(receiver, hook, &self.pending, self.spool.as_deref());
+              } else {
+                // Events captured while draining are queued if the policy includes them, so process them too.
+                loop {
+                  if !self.process_pending(receiver, shutdown) {
+                    return false;
+                  }
+                  if self.options.shutdown_capture_policy != ShutdownCapturePolicy::Include
+                    || receiver.is_empty()
+                    || Instant::now() >= deadline
+                  {
+                    break;
+                  }
+                }
+              }
+              #[cfg(feature = "metrics")]
+              self.counters.export_queue_depth(receiver.len());
+              sender.send(()).ok();
+              return false;
+            }
+            Control::Pause => {
+              if !paused {
+                paused = true;
+                self.notify(WorkerLifecycle::Paused);
+              }
+            }
+            Control::Resume => {
+              if paused {
+                paused = false;
+                self.notify(WorkerLifecycle::Resumed);
+              }
+            }
+            Control::AddBeforeSend(hook) => self.options.before_send.push(hook),
+            Control::Shutdown => {
+              log::trace!("Shutting down worker thread");
+              return false;
+            }
+          }
+        }
+        recv(tasks) -> task => {
+          let Ok(task) = task else { return false };
+          let tasks: Vec<Task> = std::iter::once(task)
+            .chain(receiver.try_iter().take(self.concurrency() - 1))
+            .collect();
+          #[cfg(feature = "metrics")]
+          self.counters.export_queue_depth(receiver.len());
+          self.process_tasks(tasks);
+        }
+        default(idle_timeout) => {
+          idle.store(true, Ordering::SeqCst);
+          // A message sent before the worker was marked idle doesn't wake it, so it keeps running to process it,
+          // unless it is already being woken.
+          if (receiver.is_empty() && control.is_empty())
+            || idle.compare_exchange(true, false, Ordering::SeqCst, Ordering::SeqCst).is_err()
+          {
+            log::trace!("Worker thread exiting after idling for {idle_timeout:?}");
+            return true;
+          }
+        }
+      }
+    }
+  }
+
+  /// Processes the tasks already in the queue.
+  ///
+  /// Only tasks enqueued before the call are processed, so a busy producer can't stall a flush.
+  /// Returns `false` if the worker was shut down in the meantime.
+  fn process_pending(&mut self, receiver: &Receiver<Task>, shutdown: &AtomicBool) -> bool {
+    let concurrency = self.concurrency();
+    let mut pending = receiver.try_iter().take(receiver.len()).peekable();
+    while pending.peek().is_some() {
+      if shutdown.load(Ordering::SeqCst) {
+        return false;
+      }
+      let tasks: Vec<Task> = pending.by_ref().take(concurrency).collect();
+      #[cfg(feature = "metrics")]
+      self.counters.export_queue_depth(receiver.len());
+      self.process_tasks(tasks);
+    }
+    true
+  }
+
+  /// Invokes the lifecycle hook, if any.
+  fn notify(&self, lifecycle: WorkerLifecycle) {
+    if let Some(hook) = &self.options.lifecycle_hook
+      && std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| hook(lifecycle))).is_err()
+    {
+      log::error!("Panic in lifecycle hook");
+    }
+  }
+
+  /// Returns the configured API key.
+  const fn api_key(&self) -> &ApiKey {
+    self.options.api_key.as_ref().expect("API key must be present")
+  }
+
+  /// Returns the maximum number of requests sent at once.
+  fn concurrency(&self) -> usize {
+    self.options.max_concurrent_requests.max(1)
+  }
+
+  /// Returns the parts of the state needed to deliver requests.
+  const fn dispatcher(&self) -> Dispatcher<'_> {
+    Dispatcher {
+      transport: &self.transport,
+      host: &self.options.host,
+      api_key: self.api_key(),
+      retry: self.options.retry.as_ref(),
+    }
+  }
+
+  /// Processes tasks from the event queue, then sends their requests.
+  fn process_tasks(&mut self, tasks: impl IntoIterator<Item = Task>) {
+    let mut requests = Vec::new();
+    let mut records = Vec::new();
+    let mut tasks_count = 0;
+    let mut events_count = 0;
+    for task in tasks {
+      events_count += task.events_count();
+      records.extend(task.spool_record());
+      self.process_task(task, &mut requests);
+      tasks_count += 1;
+    }
+    if self.options.merge_person_properties {
+      let mut events: Vec<&mut Event> = requests
+        .iter_mut()
+        .flat_map(|request| match request {
+          Request::Capture(event, None) => std::slice::from_mut(event),
+          Request::Batch(events, None) => events.as_mut_slice(),
+          Request::Capture(..) | Request::Batch(..) | Request::Raw(_) => &mut [],
+        })
+        .collect();
+      merge_person_properties(&mut events);
+    }
+    self.send(&requests);
+    if let Some(spool) = &self.spool
+      && !records.is_empty()
+    {
+      spool.lock().unwrap_or_else(PoisonError::into_inner).remove(&records);
+    }
+    self.pending.complete(tasks_count, events_count);
+  }
 
-  match serde_json::to_string(&payload) {
-    Ok(body) => {
-      log::trace!("Serialized payload size: {} bytes", body.len());
-      let result = client
-        .post(&url)
-        .header("Content-Type", "application/json")
-        .body(body)
-        .send();
-
-      match result {
-        Ok(response) if response.status().is_success() => {
-          log::trace!("Capture request successful: status {}", response.status());
+  /// Processes a single task from the event queue, collecting the requests to send.
+  ///
+  /// Batches awaiting PostHog's response are sent right away.
+  fn process_task(&mut self, task: Task, requests: &mut Vec<Request>) {
+    match task {
+      Task::Capture(mut event, destination, _) => {
+        log::trace!("Processing capture task for event: {}", event.event);
+        saturate_event(&mut event, &self.context);
+        if let Some(mut event) =
+          guard_timestamp(&self.options, event).and_then(|event| apply_before_send(&mut self.options, event))
+        {
+          self.finalize_event(&mut event);
+          if self.is_duplicate(&event) {
+            return;
+          }
+          let Some(in_batch) = self.fit_event(&mut event) else {
+            return;
+          };
+          self.tap(&event);
+          if destination.is_none() {
+            for mirror in &self.mirrors {
+              mirror.capture(event.clone(), None);
+            }
+          }
+          requests.push(if in_batch || self.options.historical_migration {
+            Request::Batch(vec![event], destination)
+          } else {
+            Request::Capture(event, destination)
+          });
+        } else {
+          log::trace!("Event was dropped by timestamp guard or before_send hook");
+          Counters::add(&self.counters.filtered, 1);
         }
-        Ok(response) if response.status().as_u16() == 401 => {
-          log::error!("PostHog authentication failed: invalid API key");
+      }
+      Task::CaptureWithAttachments(mut event, attachments) => {
+        self.upload_attachments(&mut event, attachments);
+        self.process_task(Task::Capture(event, None, None), requests);
+      }
+      Task::Batch(events, _) => {
+        log::trace!("Processing batch task with {} events", events.len());
+        let events = self.prepare_batch(events);
+        requests.extend(
+          self
+            .split_batch(events)
+            .into_iter()
+            .map(|events| Request::Batch(events, None)),
+        );
+      }
+      Task::BatchWithResponse(events, reply) => {
+        log::trace!("Processing batch task with {} events and response", events.len());
+        let mut events = self.prepare_batch(events);
+        if self.options.merge_person_properties {
+          merge_person_properties(&mut events.iter_mut().collect::<Vec<_>>());
         }
-        Ok(response) => {
-          log::error!("PostHog request failed with status: {}", response.status());
+        let events_count = events.len();
+        let result = if events.is_empty() {
+          Ok(BatchResponse { status: None, sent: 0 })
+        } else if self.auth_disabled {
+          Err(Error::Auth)
+        } else if self.allows_request(None) {
+          self.dispatcher().deliver_batch_with_response(events)
+        } else {
+          Err(Error::CircuitOpen)
+        };
+        if events_count > 0 {
+          let delivery = result.as_ref().map_or_else(Error::delivery, |_| Delivery::Sent);
+          self.record_delivery(delivery, events_count, None);
         }
-        Err(e) => {
-          log::error!("Failed to send event to PostHog: {e}");
+        let _ = reply.send(result);
+      }
+      Task::Raw(mut event) => {
+        log::trace!("Processing raw capture task");
+        self.finalize_raw(&mut event);
+        for mirror in &self.mirrors {
+          mirror.capture_raw(event.clone());
         }
+        requests.push(Request::Raw(event));
       }
     }
-    Err(e) => {
-      log::error!("Failed to serialize event: {e}");
+  }
+
+  /// Sends requests up to `max_concurrent_requests` at a time, and records their outcome.
+  fn send(&mut self, requests: &[Request]) {
+    for requests in requests.chunks(self.concurrency()) {
+      // Requests to the configured destination are skipped while the circuit is open, except for a single probe.
+      let allowed: Vec<bool> = requests
+        .iter()
+        .map(|request| self.allows_request(request.destination()))
+        .collect();
+      let sendable: Vec<&Request> = requests
+        .iter()
+        .zip(&allowed)
+        .filter_map(|(request, &allowed)| allowed.then_some(request))
+        .collect();
+      let mut deliveries = self.dispatcher().deliver_all(&sendable).into_iter();
+      for (request, allowed) in requests.iter().zip(allowed) {
+        let delivery = if allowed {
+          deliveries.next().unwrap_or(Delivery::Skipped)
+        } else {
+          Delivery::Skipped
+        };
+        self.record_delivery(delivery, request.events_count(), request.destination());
+      }
     }
   }
-}
 
-/// Sends a batch of events to PostHog via `/batch/`.
-fn send_batch(client: &reqwest::blocking::Client, options: &ClientOptions, events: &[Event]) {
-  let url = options.host.batch_url();
-  let payload = BatchPayload {
-    api_key: options.api_key.as_ref().expect("API key must be present").as_str(),
-    batch: events
-      .iter()
-      .map(|event| {
-        let mut properties = event.properties.clone();
-        properties.insert("distinct_id".to_string(), Value::String(event.distinct_id.clone()));
-        BatchEvent {
-          event: event.event.clone(),
-          timestamp: event.timestamp.clone(),
-          properties,
+  /// Uploads the attachments of an event with the `attachment_uploader` hook, setting their reference URLs as properties.
+  fn upload_attachments(&mut self, event: &mut Event, attachments: Vec<Attachment>) {
+    let log_levels = self.options.log_levels;
+    let Some(uploader) = &mut self.options.attachment_uploader else {
+      log::warn!(
+        "Dropping {} attachments of {} event: no attachment_uploader is configured",
+        attachments.len(),
+        event.event
+      );
+      return;
+    };
+
+    for attachment in attachments {
+      match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| uploader(event, &attachment))) {
+        Ok(Ok(url)) => {
+          log::trace!("Uploaded `{}` attachment of {} event", attachment.property, event.event);
+          event.properties.insert(attachment.property, Value::String(url));
         }
+        Ok(Err(e)) => log_at!(
+          log_levels.send_failure,
+          "Failed to upload `{}` attachment of {} event: {e}",
+          attachment.property,
+          event.event
+        ),
+        Err(_) => log::error!(
+          "Panic in attachment_uploader hook, dropping `{}` attachment",
+          attachment.property
+        ),
+      }
+    }
+  }
+
+  /// Enriches the events of a batch and applies the `timestamp_guard` and `before_send` hooks,
+  /// returning the events to send.
+  fn prepare_batch(&mut self, events: Vec<Event>) -> Vec<Event> {
+    let events_count = events.len();
+    let mut events: Vec<Event> = events
+      .into_iter()
+      .filter_map(|mut event| {
+        saturate_event(&mut event, &self.context);
+        guard_timestamp(&self.options, event).and_then(|event| apply_before_send(&mut self.options, event))
       })
-      .collect(),
-  };
+      .collect();
+    for event in &mut events {
+      self.finalize_event(event);
+    }
+    events.retain(|event| !self.is_duplicate(event));
+    for event in &events {
+      self.tap(event);
+    }
+    if !events.is_empty() {
+      for mirror in &self.mirrors {
+        mirror.batch(events.clone());
+      }
+    }
+    if events_count != events.len() {
+      log::trace!(
+        "{} events were dropped by timestamp guard or before_send hook",
+        events_count - events.len()
+      );
+      Counters::add(&self.counters.filtered, events_count - events.len());
+    }
+    events
+  }
+
+  /// Returns `true` if a request to the given destination may be sent.
+  ///
+  /// Only requests to the configured destination go through the circuit breaker.
+  fn allows_request(&mut self, destination: Option<&Destination>) -> bool {
+    !self.auth_disabled
+      && (destination.is_some() || self.circuit_breaker.as_mut().is_none_or(CircuitBreaker::allows_request))
+  }
+
+  /// Updates the counters and the circuit breaker with the outcome of a delivery.
+  ///
+  /// The destination is the one the events were sent to, if it differs from the configured one,
+  /// in which case the circuit breaker is left as is.
+  fn record_delivery(&mut self, delivery: Delivery, events_count: usize, destination: Option<&Destination>) {
+    match delivery {
+      Delivery::Sent => Counters::add(&self.counters.sent, events_count),
+      Delivery::Skipped => {
+        log::trace!("Circuit is open or sending is disabled, {events_count} events were dropped");
+        Counters::add(&self.counters.failed, events_count);
+      }
+      Delivery::Rejected | Delivery::Unavailable => Counters::add(&self.counters.failed, events_count),
+      Delivery::Unauthorized => {
+        Counters::add(&self.counters.failed, events_count);
+        self.handle_auth_error(destination);
+      }
+    }
+
+    if destination.is_none()
+      && let Some(circuit_breaker) = &mut self.circuit_breaker
+    {
+      match delivery {
+        Delivery::Sent | Delivery::Rejected | Delivery::Unauthorized => circuit_breaker.record_success(),
+        Delivery::Unavailable => circuit_breaker.record_failure(),
+        Delivery::Skipped => {}
+      }
+      self
+        .counters
+        .circuit_open
+        .store(circuit_breaker.is_open(), Ordering::Relaxed);
+    }
+  }
+
+  /// Reports an API key rejected by PostHog to the `on_auth_error` hook, and disables sending if configured.
+  fn handle_auth_error(&mut self, destination: Option<&Destination>) {
+    let api_key = destination.map_or_else(|| self.api_key(), |destination| &destination.api_key);
+    if let Some(hook) = &self.options.on_auth_error
+      && std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| hook(api_key))).is_err()
+    {
+      log::error!("Panic in on_auth_error hook");
+    }
+
+    if destination.is_none() && self.options.disable_on_auth_error && !self.auth_disabled {
+      log::error!("PostHog rejected the API key, no more events will be sent");
+      self.auth_disabled = true;
+    }
+  }
+
+  /// Prepares an event that passed all `before_send` hooks for delivery.
+  ///
+  /// Assigns the `default_distinct_id` or an anonymous distinct ID if none was set.
+  /// Removes `null`-valued and denied properties if configured.
+  /// Disables person processing for anonymous events, unless configured otherwise or already set.
+  /// Also assigns an `$insert_id` if retries are enabled with [`RetryPolicy::GenerateInsertId`].
+  fn finalize_event(&self, event: &mut Event) {
+    if event.distinct_id.is_none() {
+      event.distinct_id.clone_from(&self.options.default_distinct_id);
+    }
+    let anonymous = event.distinct_id.is_none() || event.is_anonymous();
+    event.distinct_id.get_or_insert_with(anonymous_id);
+    if anonymous && !self.options.process_anonymous_persons {
+      event
+        .properties
+        .entry(PROCESS_PERSON_PROFILE.to_string())
+        .or_insert(Value::Bool(false));
+    }
+
+    if self.options.strip_null_properties {
+      event.properties.retain(|_, value| !value.is_null());
+    }
+
+    for key in &self.options.property_deny_list {
+      if event.properties.remove(key).is_some() {
+        log::trace!("Stripped denied `{key}` property from {} event", event.event);
+      }
+    }
+
+    if self
+      .options
+      .retry
+      .as_ref()
+      .is_some_and(|retry| retry.policy == RetryPolicy::GenerateInsertId)
+    {
+      event
+        .properties
+        .entry(INSERT_ID.to_string())
+        .or_insert_with(|| Value::String(uuid::Uuid::new_v4().to_string()));
+    }
+  }
+
+  /// Removes `null`-valued and denied properties of a raw event if configured.
+  fn finalize_raw(&self, event: &mut Value) {
+    let Some(properties) = event.get_mut("properties").and_then(Value::as_object_mut) else {
+      return;
+    };
+    if self.options.strip_null_properties {
+      properties.retain(|_, value| !value.is_null());
+    }
+    for key in &self.options.property_deny_list {
+      if properties.remove(key).is_some() {
+        log::trace!("Stripped denied `{key}` property from raw event");
+      }
+    }
+  }
+
+  /// Returns `true` if the event's `$insert_id` was recently seen, counting it as deduplicated.
+  fn is_duplicate(&mut self, event: &Event) -> bool {
+    let (Some(dedup), Some(Value::String(insert_id))) = (&mut self.dedup, event.properties.get(INSERT_ID)) else {
+      return false;
+    };
+    if dedup.insert(insert_id) {
+      return false;
+    }
+    log::trace!("Dropping {} event with duplicate insert ID {insert_id}", event.event);
+    Counters::add(&self.counters.deduplicated, 1);
+    true
+  }
+
+  /// Applies the `oversized_event_policy` if the event exceeds `max_event_bytes`.
+  ///
+  /// Returns `None` if the event was dropped, or whether it must be sent through the batch endpoint.
+  fn fit_event(&self, event: &mut Event) -> Option<bool> {
+    let Some(max_bytes) = self.options.max_event_bytes else {
+      return Some(false);
+    };
+    let event_bytes = estimated_size(event);
+    if event_bytes <= max_bytes {
+      return Some(false);
+    }
+    Counters::add(&self.counters.oversized, 1);
 
-  match serde_json::to_string(&payload) {
-    Ok(body) => {
-      log::trace!("Serialized batch payload size: {} bytes", body.len());
-      let result = client
-        .post(&url)
-        .header("Content-Type", "application/json")
-        .body(body)
-        .send();
-
-      match result {
-        Ok(response) if response.status().is_success() => {
-          log::trace!("Batch request successful: status {}", response.status());
+    match self.options.oversized_event_policy {
+      OversizedEventPolicy::Drop => {
+        log::warn!(
+          "Dropping {} event of {event_bytes} bytes, exceeding the limit of {max_bytes} bytes",
+          event.event
+        );
+        None
+      }
+      OversizedEventPolicy::StripLargeProperties => {
+        let mut keys: Vec<(usize, String)> = event
+          .properties
+          .iter()
+          .map(|(key, value)| (estimated_size(value), key.clone()))
+          .collect();
+        keys.sort_unstable_by(|a, b| b.cmp(a));
+
+        let mut stripped = Vec::new();
+        for (_, key) in keys {
+          if estimated_size(event) <= max_bytes {
+            break;
+          }
+          event.properties.remove(&key);
+          stripped.push(key);
         }
-        Ok(response) if response.status().as_u16() == 401 => {
-          log::error!("PostHog authentication failed: invalid API key");
+        log::warn!(
+          "Stripped properties {stripped:?} from {} event of {event_bytes} bytes, exceeding the limit of {max_bytes} bytes",
+          event.event
+        );
+        Some(false)
+      }
+      OversizedEventPolicy::SendInBatch => {
+        log::trace!("Sending {} event of {event_bytes} bytes in a batch", event.event);
+        Some(true)
+      }
+    }
+  }
+
+  /// Splits a batch into chunks whose estimated serialized size doesn't exceed `max_batch_bytes`, if configured.
+  ///
+  /// Events larger than the limit are sent in a chunk of their own. Returns no chunks for an empty batch.
+  fn split_batch(&self, events: Vec<Event>) -> Vec<Vec<Event>> {
+    if events.is_empty() {
+      return Vec::new();
+    }
+    let Some(max_bytes) = self.options.max_batch_bytes else {
+      return vec![events];
+    };
+
+    let mut chunks = Vec::new();
+    let mut chunk = Vec::new();
+    let mut chunk_bytes = 0;
+    for event in events {
+      let event_bytes = estimated_size(&event);
+      if !chunk.is_empty() && chunk_bytes + event_bytes > max_bytes {
+        chunks.push(std::mem::take(&mut chunk));
+        chunk_bytes = 0;
+      }
+      chunk_bytes += event_bytes;
+      chunk.push(event);
+    }
+    chunks.push(chunk);
+
+    if chunks.len() > 1 {
+      log::trace!("Split batch into {} requests of up to {max_bytes} bytes", chunks.len());
+    }
+    chunks
+  }
+
+  /// Sends a copy of the event to the tap channel, if configured.
+  fn tap(&self, event: &Event) {
+    if let Some(tap) = &self.options.tap
+      && tap.send(event.clone()).is_err()
+    {
+      log::trace!("Tap receiver is disconnected, event was not mirrored");
+    }
+  }
+}
+
+/// Parts of the worker state needed to deliver requests, shared by the threads sending concurrent requests.
+struct Dispatcher<'a> {
+  transport: &'a Transport,
+  host: &'a Host,
+  api_key: &'a ApiKey,
+  retry: Option<&'a RetryConfig>,
+}
+
+impl Dispatcher<'_> {
+  /// Delivers requests concurrently, each on its own thread, returning their outcomes in order.
+  ///
+  /// A single request is delivered on the current thread.
+  fn deliver_all(&self, requests: &[&Request]) -> Vec<Delivery> {
+    if let [request] = requests {
+      return vec![self.deliver(request)];
+    }
+
+    thread::scope(|scope| {
+      // All requests must be in flight before waiting for any of them.
+      #[allow(clippy::needless_collect)]
+      let handles: Vec<_> = requests
+        .iter()
+        .map(|request| {
+          thread::Builder::new()
+            .name(THREAD_NAME.into())
+            .spawn_scoped(scope, move || self.deliver(request))
+            .map_err(|e| {
+              log::warn!("Failed to spawn PostHog sender thread, sending on the worker thread: {e}");
+              request
+            })
+        })
+        .collect();
+      handles
+        .into_iter()
+        .map(|handle| match handle {
+          Ok(handle) => handle.join().unwrap_or_else(|_| {
+            log::error!("Panic while sending PostHog request");
+            Delivery::Rejected
+          }),
+          Err(request) => self.deliver(request),
+        })
+        .collect()
+    })
+  }
+
+  /// Delivers a request with the configured transport.
+  fn deliver(&self, request: &Request) -> Delivery {
+    match self.transport {
+      Transport::Http(client) => match request {
+        Request::Capture(event, destination) => {
+          let (host, api_key) = self.target(destination.as_ref());
+          self.send_with_retry(
+            client,
+            has_insert_id(event),
+            client.prepare_capture(host, api_key, event),
+          )
         }
-        Ok(response) => {
-          log::error!("PostHog batch request failed with status: {}", response.status());
+        Request::Batch(events, destination) => {
+          let (host, api_key) = self.target(destination.as_ref());
+          self.send_with_retry(
+            client,
+            events.iter().all(has_insert_id),
+            client.prepare_batch(host, api_key, events),
+          )
         }
-        Err(e) => {
-          log::error!("Failed to send batch to PostHog: {e}");
+        Request::Raw(event) => {
+          let idempotent = event
+            .get("properties")
+            .and_then(|properties| properties.get(INSERT_ID))
+            .is_some();
+          self.send_with_retry(client, idempotent, client.prepare_raw(self.host, self.api_key, event))
         }
+      },
+      #[cfg(feature = "test-util")]
+      Transport::Memory(captured) => {
+        let events = match request {
+          Request::Capture(event, _) => vec![event.clone()],
+          Request::Batch(events, _) => events.clone(),
+          Request::Raw(event) => match serde_json::from_value(event.clone()) {
+            Ok(event) => vec![event],
+            Err(e) => {
+              log::error!("Failed to record raw event: {e}");
+              return Delivery::Rejected;
+            }
+          },
+        };
+        captured
+          .lock()
+          .unwrap_or_else(std::sync::PoisonError::into_inner)
+          .extend(events);
+        Delivery::Sent
+      }
+    }
+  }
+
+  /// Delivers a batch of events in a single request with the configured transport, returning PostHog's response.
+  #[cfg_attr(not(feature = "test-util"), allow(clippy::needless_pass_by_value))]
+  fn deliver_batch_with_response(&self, events: Vec<Event>) -> Result<BatchResponse, Error> {
+    match self.transport {
+      Transport::Http(client) => client.send_batch_with_response(self.host, self.api_key, &events),
+      #[cfg(feature = "test-util")]
+      Transport::Memory(captured) => {
+        let sent = events.len();
+        captured
+          .lock()
+          .unwrap_or_else(std::sync::PoisonError::into_inner)
+          .extend(events);
+        Ok(BatchResponse {
+          status: Some(200),
+          sent,
+        })
+      }
+    }
+  }
+
+  /// Returns the host and API key of the given destination, or the configured ones.
+  fn target<'a>(&'a self, destination: Option<&'a Destination>) -> (&'a Host, &'a ApiKey) {
+    destination.map_or((self.host, self.api_key), |destination| {
+      (&destination.host, &destination.api_key)
+    })
+  }
+
+  /// Sends a prepared request, retrying it with the same payload while PostHog is unavailable, if configured.
+  ///
+  /// Events are enriched and assigned their `$insert_id` once, before the request is prepared,
+  /// so every attempt sends the exact same body and PostHog can deduplicate them.
+  fn send_with_retry(
+    &self,
+    client: &HttpClient,
+    idempotent: bool,
+    request: Result<PreparedRequest, Error>,
+  ) -> Delivery {
+    request.map_or_else(
+      |e| e.delivery(),
+      |request| self.with_retry(idempotent, || client.send(&request)),
+    )
+  }
+
+  /// Performs a delivery attempt, retrying it with backoff while PostHog is unavailable, if configured.
+  ///
+  /// Requests are only retried if they are `idempotent`, i.e. all their events have an `$insert_id`.
+  fn with_retry(&self, idempotent: bool, mut attempt: impl FnMut() -> Delivery) -> Delivery {
+    let mut delivery = attempt();
+    let Some(retry) = self.retry else {
+      return delivery;
+    };
+    if !idempotent {
+      if delivery == Delivery::Unavailable {
+        log::trace!("Not retrying PostHog request with events missing `{INSERT_ID}`");
+      }
+      return delivery;
+    }
+
+    for attempt_number in 1..=retry.max_retries {
+      if delivery != Delivery::Unavailable {
+        break;
       }
+      let backoff = retry.backoff(attempt_number);
+      log::warn!(
+        "Retrying PostHog request in {backoff:?} (attempt {attempt_number} of {})",
+        retry.max_retries
+      );
+      thread::sleep(backoff);
+      delivery = attempt();
+    }
+    delivery
+  }
+}
+
+/// Sends a single event synchronously with a throwaway HTTP client, without spawning a worker thread.
+///
+/// The event is prepared like captured events, except for the `before_enqueue` hooks, and sent in a single request
+/// without retries. Returns `Ok(())` without sending anything if a `before_send` hook discarded it.
+pub fn send_once(mut options: ClientOptions, mut event: Event) -> Result<(), Error> {
+  if options.auto_timestamp {
+    event.timestamp.get_or_insert_with(|| Timestamp::now().into());
+  }
+  let http_client = HttpClient::new(&mut options);
+  let mut state = WorkerState {
+    circuit_breaker: None,
+    dedup: None,
+    spool: None,
+    auth_disabled: false,
+    context: context::context(&options),
+    options,
+    transport: Transport::Http(http_client),
+    counters: Arc::default(),
+    pending: Arc::default(),
+    mirrors: Vec::new(),
+  };
+
+  let events = state.prepare_batch(vec![event]);
+  if events.is_empty() {
+    return Ok(());
+  }
+  let dispatcher = Dispatcher {
+    retry: None,
+    ..state.dispatcher()
+  };
+  dispatcher.deliver_batch_with_response(events).map(|_| ())
+}
+
+/// Returns `true` if the event has an `$insert_id` property, making it safe to retry.
+fn has_insert_id(event: &Event) -> bool {
+  event.properties.contains_key(INSERT_ID)
+}
+
+/// Removes the tasks already in the queue, returning their events with the number of tasks and events removed.
+///
+/// Blocking batches are answered with [`Error::Drained`], and raw events that can't be parsed are dropped.
+fn take_queued(receiver: &Receiver<Task>, spool: Option<&Mutex<Spool>>) -> (Vec<Event>, usize, usize) {
+  let tasks_count = receiver.len();
+  let mut taken_tasks = 0;
+  let mut events_count = 0;
+  let mut events = Vec::new();
+  let mut records = Vec::new();
+  for task in receiver.try_iter().take(tasks_count) {
+    taken_tasks += 1;
+    events_count += task.events_count();
+    records.extend(task.spool_record());
+    match task {
+      Task::Capture(event, ..) | Task::CaptureWithAttachments(event, _) => events.push(event),
+      Task::Batch(batch, _) => events.extend(batch),
+      Task::BatchWithResponse(batch, reply) => {
+        events.extend(batch);
+        let _ = reply.send(Err(Error::Drained));
+      }
+      Task::Raw(event) => match serde_json::from_value(event) {
+        Ok(event) => events.push(event),
+        Err(e) => log::warn!("Dropping raw PostHog event taken from the queue: {e}"),
+      },
+    }
+  }
+  if let Some(spool) = spool
+    && !records.is_empty()
+  {
+    spool.lock().unwrap_or_else(PoisonError::into_inner).remove(&records);
+  }
+  (events, taken_tasks, events_count)
+}
+
+/// Passes the events already in the queue to the `on_shutdown_remaining` hook instead of sending them.
+fn hand_over_pending(
+  receiver: &Receiver<Task>,
+  hook: ShutdownRemainingFn,
+  pending: &Pending,
+  spool: Option<&Mutex<Spool>>,
+) {
+  let (events, tasks_count, events_count) = take_queued(receiver, spool);
+  log::trace!(
+    "Handing {} remaining events to on_shutdown_remaining hook",
+    events.len()
+  );
+  if std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| hook(events))).is_err() {
+    log::error!("Panic in on_shutdown_remaining hook");
+  }
+  pending.complete(tasks_count, events_count);
+}
+
+/// Applies all `before_send` hooks to an event.
+///
+/// Returns `Some(event)` if the event should be sent, `None` if it was discarded.
+/// The event is discarded on panic.
+fn apply_before_send(options: &mut ClientOptions, mut event: Event) -> Option<Event> {
+  for hook in &mut options.before_send {
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| hook(event))) {
+      Ok(Some(e)) => event = e,
+      Ok(None) => return None,
+      Err(_) => {
+        log::error!("Panic in before_send hook, discarding event");
+        return None;
+      }
+    }
+  }
+  Some(event)
+}
+
+/// Applies the `timestamp_guard` option to an event.
+///
+/// Returns `Some(event)` with its timestamp clamped if needed, or `None` if it was dropped.
+fn guard_timestamp(options: &ClientOptions, mut event: Event) -> Option<Event> {
+  let (Some(guard), Some(timestamp)) = (&options.timestamp_guard, &event.timestamp) else {
+    return Some(event);
+  };
+  let Some(issue) = guard.check(timestamp, options.historical_migration) else {
+    return Some(event);
+  };
+  match guard.policy {
+    SkewedTimestampPolicy::Clamp => {
+      log::warn!(
+        "Timestamp {timestamp} of {} event is {issue}, replacing it with the current time",
+        event.event
+      );
+      event.timestamp = Some(Timestamp::now_with_precision(options.timestamp_precision).into());
+      Some(event)
     }
-    Err(e) => {
-      log::error!("Failed to serialize batch: {e}");
+    SkewedTimestampPolicy::Drop => {
+      log::warn!("Timestamp {timestamp} of {} event is {issue}, dropping it", event.event);
+      None
     }
   }
 }
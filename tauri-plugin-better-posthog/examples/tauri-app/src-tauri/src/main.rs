@@ -2,37 +2,18 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+  let environment = if cfg!(debug_assertions) {
+    "development"
+  } else {
+    "production"
+  };
   let options = better_posthog::ClientOptions {
     api_key: option_env!("POSTHOG_API_KEY").map(Into::into),
     host: better_posthog::Host::EU,
-    before_send: vec![
-      {
-        // Initialize a scoped `Send`-compatible RNG.
-        let mut rng = fastrand::Rng::new();
-
-        // Return a `before_send` hook.
-        Box::new(move |event| {
-          let sample_rate = match event.event.as_str() {
-            "button_click" => Some(0.5), // Process only a half of `button_click` events.
-            _ => None,                   // Process all other events.
-          };
-          if let Some(sample_rate) = sample_rate
-            && rng.f64() < sample_rate
-          {
-            Some(event)
-          } else {
-            None
-          }
-        })
-      },
-      Box::new(|mut event| {
-        #[cfg(debug_assertions)]
-        event.insert_property("environment", "development");
-        #[cfg(not(debug_assertions))]
-        event.insert_property("environment", "production");
-        Some(event)
-      }),
-    ],
+    // Process only a half of `button_click` events.
+    event_policies: vec![("button_click".into(), better_posthog::EventPolicy::Sample(0.5))],
+    environment: Some(environment.into()),
+    release: Some(env!("CARGO_PKG_VERSION").into()),
     ..Default::default()
   };
   let _guard = better_posthog::init(options);
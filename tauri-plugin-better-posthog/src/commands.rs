@@ -2,7 +2,9 @@
 
 use std::collections::HashMap;
 
-use crate::PostHogExt as _;
+use tauri::Manager as _;
+
+use crate::state::PluginState;
 
 /// Request payload for the `capture` command.
 #[derive(Debug, serde::Deserialize)]
@@ -31,11 +33,28 @@ pub async fn capture<R: tauri::Runtime>(
   properties: Option<HashMap<String, serde_json::Value>>,
   app_handle: tauri::AppHandle<R>,
 ) {
-  app_handle.capture_event(CaptureRequest { event, properties });
+  if let Some(event) = prepare(&app_handle, &[CaptureRequest { event, properties }]).pop() {
+    better_posthog::events::capture(event);
+  }
 }
 
 /// Captures a batch of events from the frontend.
 #[tauri::command]
 pub async fn batch<R: tauri::Runtime>(events: Vec<CaptureRequest>, app_handle: tauri::AppHandle<R>) {
-  app_handle.batch_events(&events);
+  let events = prepare(&app_handle, &events);
+  if !events.is_empty() {
+    better_posthog::events::batch(events);
+  }
+}
+
+/// Converts events captured from the frontend to PostHog events, applying the frontend hooks.
+fn prepare<R: tauri::Runtime>(
+  app_handle: &tauri::AppHandle<R>,
+  requests: &[CaptureRequest],
+) -> Vec<better_posthog::Event> {
+  let state = app_handle.state::<PluginState>();
+  crate::to_posthog_events(app_handle, requests)
+    .into_iter()
+    .filter_map(|event| state.apply_frontend_before_send(event))
+    .collect()
 }
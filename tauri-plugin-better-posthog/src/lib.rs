@@ -56,9 +56,18 @@ mod commands;
 mod identity;
 mod state;
 
+use std::time::Duration;
+
+use better_posthog::BeforeEnqueueFn;
 pub use identity::IdentityStrategy;
 use tauri::plugin::{Builder as PluginBuilder, TauriPlugin};
-use tauri::{Manager, Runtime};
+use tauri::{Manager, RunEvent, Runtime};
+
+/// Maximum time the application exit is delayed to send the session end event.
+const SESSION_END_FLUSH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Name of the file in the app data directory keeping the keys of the events captured once.
+const ONCE_FILE_NAME: &str = ".posthog-once";
 
 /// Initializes the plugin with default settings.
 #[must_use]
@@ -69,6 +78,8 @@ pub fn init<R: Runtime>() -> TauriPlugin<R> {
 /// Builder for configuring the PostHog plugin.
 pub struct Builder<R: Runtime> {
   identity_strategy: IdentityStrategy<R>,
+  session_end_event: Option<String>,
+  frontend_before_send: Vec<BeforeEnqueueFn>,
 }
 
 impl<R: Runtime> Builder<R> {
@@ -87,19 +98,74 @@ impl<R: Runtime> Builder<R> {
     self
   }
 
+  /// Captures an event with the given name, like `$pageleave`, when the application exits.
+  ///
+  /// The event has a `$session_duration` property with the number of seconds since the plugin was initialized,
+  /// and is flushed before the application exits.
+  #[must_use]
+  pub fn session_end_event(mut self, name: impl Into<String>) -> Self {
+    self.session_end_event = Some(name.into());
+    self
+  }
+
+  /// Adds a hook to modify or filter events captured from the frontend, before they are sent to the core SDK.
+  ///
+  /// Unlike the `before_send` hooks of the client options, these hooks only apply to events captured with
+  /// the frontend `captureEvent` and `batchEvents` functions, e.g. to sample noisy webview events without
+  /// affecting the backend ones. They receive the events with the plugin context, like `$session_id`,
+  /// and run in the order they were added. Returning `None` discards the event.
+  /// If a hook panics, the event is discarded and an error is logged.
+  ///
+  /// # Example
+  ///
+  /// ```ignore
+  /// let plugin = tauri_plugin_better_posthog::Builder::new()
+  ///   .frontend_before_send(|mut event| {
+  ///     // Drop noisy events, and tag the other ones with their origin.
+  ///     if event.name() == "$mousemove" {
+  ///       return None;
+  ///     }
+  ///     event.insert_property("origin".to_string(), "webview");
+  ///     Some(event)
+  ///   })
+  ///   .build();
+  /// ```
+  #[must_use]
+  pub fn frontend_before_send<F>(mut self, hook: F) -> Self
+  where
+    F: Fn(better_posthog::Event) -> Option<better_posthog::Event> + Send + Sync + 'static,
+  {
+    self.frontend_before_send.push(Box::new(hook));
+    self
+  }
+
   /// Builds the plugin with the configured settings.
   #[must_use]
   pub fn build(self) -> TauriPlugin<R> {
+    let session_end_event = self.session_end_event;
     PluginBuilder::new("better-posthog")
       .invoke_handler(tauri::generate_handler![commands::capture, commands::batch])
       .setup(move |app, _api| {
         let distinct_id = self.identity_strategy.resolve(app);
 
-        let state = state::PluginState::new(distinct_id);
+        let once_store = app
+          .path()
+          .app_data_dir()
+          .ok()
+          .map(|dir| better_posthog::FileOnceStore::new(dir.join(ONCE_FILE_NAME)));
+
+        let state = state::PluginState::new(distinct_id, self.frontend_before_send, once_store);
         app.manage(state);
 
         Ok(())
       })
+      .on_event(move |app, event| {
+        if let (RunEvent::Exit, Some(name)) = (event, &session_end_event) {
+          let session_duration = app.state::<state::PluginState>().session_duration();
+          app.capture_event(SessionEnd { name, session_duration });
+          better_posthog::flush(SESSION_END_FLUSH_TIMEOUT);
+        }
+      })
       .build()
   }
 }
@@ -108,6 +174,8 @@ impl<R: Runtime> Default for Builder<R> {
   fn default() -> Self {
     Self {
       identity_strategy: IdentityStrategy::default(),
+      session_end_event: None,
+      frontend_before_send: Vec::new(),
     }
   }
 }
@@ -121,6 +189,12 @@ pub trait PostHogExt<R: Runtime> {
 
   /// Captures a batch of events efficiently.
   fn batch_events(&self, events: &[impl PostHogEvent]);
+
+  /// Captures an event only once per installation, like an `$app_installed` event on the first run.
+  ///
+  /// The keys of the events already captured are kept in the `.posthog-once` file of the app data directory,
+  /// see [`better_posthog::events::capture_once`]. Returns `true` if the event was accepted into the queue.
+  fn capture_event_once(&self, key: &str, event: impl PostHogEvent) -> bool;
 }
 
 impl<R: Runtime, T: Manager<R>> PostHogExt<R> for T {
@@ -129,47 +203,64 @@ impl<R: Runtime, T: Manager<R>> PostHogExt<R> for T {
   }
 
   fn batch_events(&self, events: &[impl PostHogEvent]) {
+    better_posthog::events::batch(to_posthog_events(self, events));
+  }
+
+  fn capture_event_once(&self, key: &str, event: impl PostHogEvent) -> bool {
     let state = self.state::<state::PluginState>();
-    let package_info = self.package_info();
-
-    let distinct_id = state.distinct_id();
-    let session_id = state.session_id();
-
-    better_posthog::events::batch(
-      events
-        .iter()
-        .map(|event| {
-          let event_name = event.name();
-          let properties = event.properties();
-
-          #[allow(clippy::option_if_let_else)]
-          let mut event = match distinct_id {
-            Some(id) => better_posthog::Event::new(event_name, id),
-            None => better_posthog::Event::new_anonymous(event_name),
-          };
-
-          for (key, value) in properties {
-            event.insert_property(key, value);
-          }
-
-          event.insert_property("$session_id".to_string(), session_id);
-
-          event.insert_property("$app".to_string(), package_info.name.clone());
-          event.insert_property("$app_version".to_string(), package_info.version.to_string());
-
-          #[cfg(target_os = "windows")]
-          event.insert_property("$browser".to_string(), "webview2");
-          #[cfg(not(target_os = "windows"))]
-          event.insert_property("$browser".to_string(), "webkit");
-          event.insert_property("$browser_version".to_string(), tauri::webview_version().ok());
-
-          event
-        })
-        .collect(),
-    );
+    let Some(store) = state.once_store() else {
+      log::warn!("PostHog `{key}` event not captured: failed to resolve app data directory");
+      return false;
+    };
+    to_posthog_events(self, &[event])
+      .pop()
+      .is_some_and(|event| better_posthog::events::capture_once_with(store, key, event))
   }
 }
 
+/// Converts events to PostHog events, with the identity and context managed by the plugin.
+fn to_posthog_events<R: Runtime>(
+  manager: &impl Manager<R>,
+  events: &[impl PostHogEvent],
+) -> Vec<better_posthog::Event> {
+  let state = manager.state::<state::PluginState>();
+  let package_info = manager.package_info();
+
+  let distinct_id = state.distinct_id();
+  let session_id = state.session_id();
+
+  events
+    .iter()
+    .map(|event| {
+      let event_name = event.name();
+      let properties = event.properties();
+
+      #[allow(clippy::option_if_let_else)]
+      let mut event = match distinct_id {
+        Some(id) => better_posthog::Event::new(event_name, id),
+        None => better_posthog::Event::new_anonymous(event_name),
+      };
+
+      for (key, value) in properties {
+        event.insert_property(key, value);
+      }
+
+      event.insert_property("$session_id".to_string(), session_id);
+
+      event.insert_property("$app".to_string(), package_info.name.clone());
+      event.insert_property("$app_version".to_string(), package_info.version.to_string());
+
+      #[cfg(target_os = "windows")]
+      event.insert_property("$browser".to_string(), "webview2");
+      #[cfg(not(target_os = "windows"))]
+      event.insert_property("$browser".to_string(), "webkit");
+      event.insert_property("$browser_version".to_string(), tauri::webview_version().ok());
+
+      event
+    })
+    .collect()
+}
+
 /// Trait for defining custom reusable PostHog events.
 ///
 /// # Example
@@ -203,3 +294,22 @@ pub trait PostHogEvent {
   /// Returns the custom properties associated with the event.
   fn properties(&self) -> std::collections::HashMap<String, serde_json::Value>;
 }
+
+/// Event captured when the application exits, see [`Builder::session_end_event`].
+struct SessionEnd<'a> {
+  name: &'a str,
+  session_duration: Duration,
+}
+
+impl PostHogEvent for SessionEnd<'_> {
+  fn name(&self) -> &str {
+    self.name
+  }
+
+  fn properties(&self) -> std::collections::HashMap<String, serde_json::Value> {
+    std::collections::HashMap::from([(
+      "$session_duration".to_string(),
+      self.session_duration.as_secs_f64().into(),
+    )])
+  }
+}
@@ -1,5 +1,9 @@
 //! Plugin state management for session and identity.
 
+use std::time::{Duration, Instant};
+
+use better_posthog::{BeforeEnqueueFn, Event, FileOnceStore};
+
 /// Plugin state containing identity and session information.
 pub struct PluginState {
   /// Resolved distinct ID.
@@ -9,24 +13,68 @@ pub struct PluginState {
   /// Session ID for the current application lifecycle.
   /// Generated as UUID v4 at the plugin initialization.
   session_id: String,
+
+  /// Time the session started, at the plugin initialization.
+  session_start: Instant,
+
+  /// Hooks applied to events captured from the frontend.
+  frontend_before_send: Vec<BeforeEnqueueFn>,
+
+  /// Store of the events captured once, in the app data directory.
+  /// `None` if the app data directory couldn't be resolved.
+  once_store: Option<FileOnceStore>,
 }
 
 impl PluginState {
-  /// Creates a new plugin state with the given distinct ID.
+  /// Creates a new plugin state with the given distinct ID, frontend hooks, and once store.
   ///
   /// Generates a new session ID (UUID v4) that persists for the application lifecycle.
-  pub fn new(distinct_id: Option<String>) -> Self {
+  pub fn new(
+    distinct_id: Option<String>,
+    frontend_before_send: Vec<BeforeEnqueueFn>,
+    once_store: Option<FileOnceStore>,
+  ) -> Self {
     Self {
       distinct_id,
       session_id: uuid::Uuid::new_v4().to_string(),
+      session_start: Instant::now(),
+      frontend_before_send,
+      once_store,
     }
   }
 
+  /// Applies the frontend hooks to an event captured from the frontend.
+  ///
+  /// Returns `None` if the event was discarded, including when a hook panics.
+  pub fn apply_frontend_before_send(&self, mut event: Event) -> Option<Event> {
+    for hook in &self.frontend_before_send {
+      match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| hook(event))) {
+        Ok(Some(e)) => event = e,
+        Ok(None) => return None,
+        Err(_) => {
+          log::error!("Panic in frontend_before_send hook, discarding event");
+          return None;
+        }
+      }
+    }
+    Some(event)
+  }
+
   /// Returns the session ID for the current application lifecycle.
   pub fn session_id(&self) -> &str {
     &self.session_id
   }
 
+  /// Returns the time elapsed since the session started.
+  pub fn session_duration(&self) -> Duration {
+    self.session_start.elapsed()
+  }
+
+  /// Returns the store of the events captured once, if the app data directory was resolved.
+  pub fn once_store(&self) -> Option<&FileOnceStore> {
+    self.once_store.as_ref()
+  }
+
   /// Returns the distinct ID if available.
   ///
   /// Returns `None` for anonymous mode.